@@ -9,18 +9,164 @@
 //! Charset names can be parsed from string, formatted to string and compared.
 //! Unregistered charsets are represented using an `Unregistered` variant.
 
+#[cfg(feature = "encoding_rs")]
+extern crate encoding_rs;
+#[cfg(feature = "http")]
+extern crate http;
+#[cfg(feature = "mime")]
+extern crate mime;
+
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::error::Error as ErrorTrait;
 use std::fmt::{self, Display};
-use std::str::FromStr;
-use std::ascii::AsciiExt;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read};
+use std::iter;
+use std::str::{self, FromStr};
+
+#[cfg(feature = "encoding_rs")]
+use encoding_rs::{CoderResult, Encoding};
+#[cfg(feature = "http")]
+use http::header::{HeaderValue, InvalidHeaderValue};
 
 pub use self::Charset::*;
 
+/// The maximum length in bytes of a charset label.
+///
+/// Longer labels are rejected with `Error::Invalid` instead of being stored
+/// in an `Unregistered` variant, so a single header can not cause an
+/// unbounded allocation. Parsers that never fail truncate them instead.
+pub const MAX_LABEL_LEN: usize = 128;
+
+/// The length of the longest canonical name of a registered charset.
+pub const MAX_NAME_LEN: usize = 13;
+
+/// An error that occurred while parsing a charset.
+///
+/// New variants may be added in minor releases, so matches must include a
+/// wildcard arm.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The charset label is empty.
+    Empty,
+    /// The charset label is not valid, contains the label truncated to
+    /// `MAX_LABEL_LEN` bytes.
+    Invalid(String),
+    /// The charset label contains non-ASCII characters.
+    NonAscii,
+    /// The charset label is not the name or alias of a registered charset,
+    /// contains the label truncated to `MAX_LABEL_LEN` bytes.
+    Unknown(String),
+    /// Bytes could not be decoded.
+    Decode(DecodeError),
+}
+
+/// A `Result` with the error type `charsets::Error`.
+pub type Result<T> = ::std::result::Result<T, Error>;
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Empty => f.write_str("empty charset label"),
+            Error::Invalid(ref label) => write!(f, "invalid charset label {:?}", label),
+            Error::NonAscii => f.write_str("non-ASCII charset label"),
+            Error::Unknown(ref label) => write!(f, "unknown charset label {:?}", label),
+            Error::Decode(ref e) => write!(f, "decode error: {}", e),
+        }
+    }
+}
+
+impl Error {
+    fn invalid(label: &[u8]) -> Error {
+        Error::Invalid(truncate_label(label))
+    }
+}
+
+impl ErrorTrait for Error {
+    fn source(&self) -> Option<&(dyn ErrorTrait + 'static)> {
+        match *self {
+            Error::Empty | Error::Invalid(_) | Error::NonAscii | Error::Unknown(_) => None,
+            Error::Decode(ref e) => Some(e),
+        }
+    }
+}
+
+/// Truncates `s` to at most `MAX_LABEL_LEN` bytes on a character boundary.
+fn truncate_str(s: &str) -> &str {
+    let mut end = s.len().min(MAX_LABEL_LEN);
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+/// Converts `label` like `String::from_utf8_lossy`, keeping only the whole
+/// characters that fit into `MAX_LABEL_LEN` bytes.
+fn truncate_label(label: &[u8]) -> String {
+    let mut s = String::with_capacity(label.len().min(MAX_LABEL_LEN));
+    for chunk in label.utf8_chunks() {
+        let replacement = Some('\u{fffd}').filter(|_| !chunk.invalid().is_empty());
+        for c in chunk.valid().chars().chain(replacement) {
+            if s.len() + c.len_utf8() > MAX_LABEL_LEN {
+                return s;
+            }
+            s.push(c);
+        }
+    }
+    s
+}
+
+/// An error returned when bytes could not be decoded with a charset.
+///
+/// Either the input was malformed or no decoder is available for the charset.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DecodeError {
+    charset: Charset,
+}
+
+impl DecodeError {
+    /// Returns the charset used for decoding.
+    pub fn charset(&self) -> &Charset {
+        &self.charset
+    }
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "input could not be decoded as {}", self.charset)
+    }
+}
+
+impl ErrorTrait for DecodeError {}
+
 /// A Mime charset.
 ///
 /// The string representation is normalised to upper case.
 ///
 /// See http://www.iana.org/assignments/character-sets/character-sets.xhtml
-#[derive(Clone, Debug, Eq, Ord, PartialOrd)]
+///
+/// New charsets may be added in minor releases. The enum is marked
+/// `#[non_exhaustive]`, so a `match` outside of this crate must include a
+/// wildcard arm:
+///
+/// ```
+/// use charsets::Charset;
+///
+/// fn is_japanese(charset: &Charset) -> bool {
+///     match *charset {
+///         Charset::ShiftJis | Charset::EucJp | Charset::Iso2022Jp => true,
+///         _ => false,
+///     }
+/// }
+///
+/// assert!(is_japanese(&Charset::EucJp));
+/// assert!(!is_japanese(&Charset::Utf8));
+/// ```
+#[derive(Clone, Debug, Eq)]
+#[non_exhaustive]
 pub enum Charset {
     /// US ASCII
     UsAscii,
@@ -72,122 +218,3554 @@ pub enum Charset {
     Koi8R,
     /// UTF-8
     Utf8,
+    /// windows-1252
+    Windows1252,
+    /// UTF-16BE
+    Utf16Be,
+    /// UTF-16LE
+    Utf16Le,
+    /// UTF-16, byte order determined by a BOM
+    Utf16,
+    /// KOI8-U
+    Koi8U,
+    /// GBK
+    Gbk,
+    /// ISO-8859-15
+    Iso885915,
+    /// GB18030
+    Gb18030,
+    /// UTF-32, byte order determined by a BOM
+    Utf32,
+    /// UTF-32BE
+    Utf32Be,
+    /// UTF-32LE
+    Utf32Le,
     /// An arbitrary charset specified as a string
     Unregistered(String),
 }
 
-const MAPPING: [(Charset, &'static str); 25] = [(UsAscii, "US-ASCII"),
- (Iso88591, "ISO-8859-1"),
- (Iso88592, "ISO-8859-2"),
- (Iso88593, "ISO-8859-3"),
- (Iso88594, "ISO-8859-4"),
- (Iso88595, "ISO-8859-5"),
- (Iso88596, "ISO-8859-6"),
- (Iso88597, "ISO-8859-7"),
- (Iso88598, "ISO-8859-8"),
- (Iso88599, "ISO-8859-9"),
- (Iso885910, "ISO-8859-10"),
- (ShiftJis, "Shift-JIS"),
- (EucJp, "EUC-JP"),
- (Iso2022Kr, "ISO-2022-KR"),
- (EucKr, "EUC-KR"),
- (Iso2022Jp, "ISO-2022-JP"),
- (Iso2022Jp2, "ISO-2022-JP-2"),
- (Iso88596E, "ISO-8859-6-E"),
- (Iso88596I, "ISO-8859-6-I"),
- (Iso88598E, "ISO-8859-8-E"),
- (Iso88598I, "ISO-8859-8-I"),
- (Gb2312, "GB2312"),
- (Big5, "5"),
- (Koi8R, "KOI8-R"),
- (Utf8, "utf-8")];
+/// A coarse family of charsets, for grouping them in user interfaces.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum CharsetGroup {
+    /// Unicode encodings
+    Unicode,
+    /// ASCII and the Latin charsets
+    Western,
+    /// Cyrillic charsets
+    Cyrillic,
+    /// Chinese, Japanese and Korean charsets
+    Cjk,
+    /// Arabic charsets
+    Arabic,
+    /// Hebrew charsets
+    Hebrew,
+    /// Greek charsets
+    Greek,
+    /// Thai charsets
+    Thai,
+    /// Unregistered charsets
+    Other,
+}
+
+/// A small `Copy` handle to a registered charset, holding its MIBenum.
+///
+/// Obtained with `Charset::code`, useful as a cheap map key.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct CharsetCode(u16);
+
+impl CharsetCode {
+    /// Returns the charset with this code.
+    pub fn charset(&self) -> Option<Charset> {
+        REGISTRY.iter()
+                .find(|info| info.mibenum == self.0)
+                .map(|info| info.charset.clone())
+    }
+}
+
+/// Information about a registered charset, see `Charset::registry`.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct CharsetInfo {
+    /// The charset.
+    pub charset: Charset,
+    /// The canonical name.
+    pub name: &'static str,
+    /// Alternative names, as listed by IANA.
+    pub aliases: &'static [&'static str],
+    /// The MIBenum assigned by IANA.
+    pub mibenum: u16,
+}
+
+static REGISTRY: [CharsetInfo; 36] = [
+    CharsetInfo {
+        charset: UsAscii,
+        name: "US-ASCII",
+        aliases: &["ANSI_X3.4-1968", "ANSI_X3.4-1986", "ISO_646.irv:1991", "ISO646-US", "us",
+                   "IBM367", "cp367", "iso-ir-6", "csASCII", "csUSASCII"],
+        mibenum: 3,
+    },
+    CharsetInfo {
+        charset: Iso88591,
+        name: "ISO-8859-1",
+        aliases: &["ISO_8859-1:1987", "ISO_8859-1", "latin1", "l1", "IBM819", "CP819",
+                   "iso-ir-100", "csISOLatin1"],
+        mibenum: 4,
+    },
+    CharsetInfo {
+        charset: Iso88592,
+        name: "ISO-8859-2",
+        aliases: &["ISO_8859-2:1987", "ISO_8859-2", "latin2", "l2", "iso-ir-101", "csISOLatin2"],
+        mibenum: 5,
+    },
+    CharsetInfo {
+        charset: Iso88593,
+        name: "ISO-8859-3",
+        aliases: &["ISO_8859-3:1988", "ISO_8859-3", "latin3", "l3", "iso-ir-109", "csISOLatin3"],
+        mibenum: 6,
+    },
+    CharsetInfo {
+        charset: Iso88594,
+        name: "ISO-8859-4",
+        aliases: &["ISO_8859-4:1988", "ISO_8859-4", "latin4", "l4", "iso-ir-110", "csISOLatin4"],
+        mibenum: 7,
+    },
+    CharsetInfo {
+        charset: Iso88595,
+        name: "ISO-8859-5",
+        aliases: &["ISO_8859-5:1988", "ISO_8859-5", "cyrillic", "iso-ir-144", "csISOLatinCyrillic"],
+        mibenum: 8,
+    },
+    CharsetInfo {
+        charset: Iso88596,
+        name: "ISO-8859-6",
+        aliases: &["ISO_8859-6:1987", "ISO_8859-6", "ECMA-114", "ASMO-708", "arabic",
+                   "iso-ir-127", "csISOLatinArabic"],
+        mibenum: 9,
+    },
+    CharsetInfo {
+        charset: Iso88597,
+        name: "ISO-8859-7",
+        aliases: &["ISO_8859-7:1987", "ISO_8859-7", "ELOT_928", "ECMA-118", "greek", "greek8",
+                   "iso-ir-126", "csISOLatinGreek"],
+        mibenum: 10,
+    },
+    CharsetInfo {
+        charset: Iso88598,
+        name: "ISO-8859-8",
+        aliases: &["ISO_8859-8:1988", "ISO_8859-8", "hebrew", "iso-ir-138", "csISOLatinHebrew"],
+        mibenum: 11,
+    },
+    CharsetInfo {
+        charset: Iso88599,
+        name: "ISO-8859-9",
+        aliases: &["ISO_8859-9:1989", "ISO_8859-9", "latin5", "l5", "iso-ir-148", "csISOLatin5"],
+        mibenum: 12,
+    },
+    CharsetInfo {
+        charset: Iso885910,
+        name: "ISO-8859-10",
+        aliases: &["ISO_8859-10:1992", "latin6", "l6", "iso-ir-157", "csISOLatin6"],
+        mibenum: 13,
+    },
+    CharsetInfo {
+        charset: ShiftJis,
+        name: "Shift-JIS",
+        aliases: &["Shift_JIS", "MS_Kanji", "csShiftJIS"],
+        mibenum: 17,
+    },
+    CharsetInfo {
+        charset: EucJp,
+        name: "EUC-JP",
+        aliases: &["Extended_UNIX_Code_Packed_Format_for_Japanese", "csEUCPkdFmtJapanese"],
+        mibenum: 18,
+    },
+    CharsetInfo {
+        charset: Iso2022Kr,
+        name: "ISO-2022-KR",
+        aliases: &["csISO2022KR"],
+        mibenum: 37,
+    },
+    CharsetInfo {
+        charset: EucKr,
+        name: "EUC-KR",
+        aliases: &["csEUCKR"],
+        mibenum: 38,
+    },
+    CharsetInfo {
+        charset: Iso2022Jp,
+        name: "ISO-2022-JP",
+        aliases: &["csISO2022JP"],
+        mibenum: 39,
+    },
+    CharsetInfo {
+        charset: Iso2022Jp2,
+        name: "ISO-2022-JP-2",
+        aliases: &["csISO2022JP2"],
+        mibenum: 40,
+    },
+    CharsetInfo {
+        charset: Iso88596E,
+        name: "ISO-8859-6-E",
+        aliases: &["ISO_8859-6-E", "csISO88596E"],
+        mibenum: 81,
+    },
+    CharsetInfo {
+        charset: Iso88596I,
+        name: "ISO-8859-6-I",
+        aliases: &["ISO_8859-6-I", "csISO88596I"],
+        mibenum: 82,
+    },
+    CharsetInfo {
+        charset: Iso88598E,
+        name: "ISO-8859-8-E",
+        aliases: &["ISO_8859-8-E", "csISO88598E"],
+        mibenum: 84,
+    },
+    CharsetInfo {
+        charset: Iso88598I,
+        name: "ISO-8859-8-I",
+        aliases: &["ISO_8859-8-I", "csISO88598I"],
+        mibenum: 85,
+    },
+    CharsetInfo {
+        charset: Gb2312,
+        name: "GB2312",
+        aliases: &["iso-ir-58", "csGB2312"],
+        mibenum: 2025,
+    },
+    CharsetInfo {
+        charset: Big5,
+        name: "Big5",
+        aliases: &["csBig5"],
+        mibenum: 2026,
+    },
+    CharsetInfo {
+        charset: Koi8R,
+        name: "KOI8-R",
+        aliases: &["csKOI8R"],
+        mibenum: 2084,
+    },
+    CharsetInfo {
+        charset: Utf8,
+        name: "utf-8",
+        aliases: &["csUTF8"],
+        mibenum: 106,
+    },
+    CharsetInfo {
+        charset: Windows1252,
+        name: "WINDOWS-1252",
+        aliases: &["cswindows1252"],
+        mibenum: 2252,
+    },
+    CharsetInfo {
+        charset: Utf16Be,
+        name: "UTF-16BE",
+        aliases: &["csUTF16BE"],
+        mibenum: 1013,
+    },
+    CharsetInfo {
+        charset: Utf16Le,
+        name: "UTF-16LE",
+        aliases: &["csUTF16LE"],
+        mibenum: 1014,
+    },
+    CharsetInfo {
+        charset: Utf16,
+        name: "UTF-16",
+        aliases: &["csUTF16"],
+        mibenum: 1015,
+    },
+    CharsetInfo {
+        charset: Koi8U,
+        name: "KOI8-U",
+        aliases: &["csKOI8U"],
+        mibenum: 2088,
+    },
+    CharsetInfo {
+        charset: Gbk,
+        name: "GBK",
+        aliases: &["CP936", "MS936", "windows-936", "csGBK"],
+        mibenum: 113,
+    },
+    CharsetInfo {
+        charset: Iso885915,
+        name: "ISO-8859-15",
+        aliases: &["ISO_8859-15", "Latin-9", "csISO885915"],
+        mibenum: 111,
+    },
+    CharsetInfo {
+        charset: Gb18030,
+        name: "GB18030",
+        aliases: &["csGB18030"],
+        mibenum: 114,
+    },
+    CharsetInfo {
+        charset: Utf32,
+        name: "UTF-32",
+        aliases: &["csUTF32"],
+        mibenum: 1017,
+    },
+    CharsetInfo {
+        charset: Utf32Be,
+        name: "UTF-32BE",
+        aliases: &["csUTF32BE"],
+        mibenum: 1018,
+    },
+    CharsetInfo {
+        charset: Utf32Le,
+        name: "UTF-32LE",
+        aliases: &["csUTF32LE"],
+        mibenum: 1019,
+    },
+];
+
+/// Charset names used by Java that are not IANA aliases.
+static JAVA_NAMES: [(Charset, &str); 21] = [(UsAscii, "ASCII"),
+ (Iso88591, "ISO8859_1"),
+ (Iso88592, "ISO8859_2"),
+ (Iso88593, "ISO8859_3"),
+ (Iso88594, "ISO8859_4"),
+ (Iso88595, "ISO8859_5"),
+ (Iso88596, "ISO8859_6"),
+ (Iso88597, "ISO8859_7"),
+ (Iso88598, "ISO8859_8"),
+ (Iso88599, "ISO8859_9"),
+ (Iso885915, "ISO8859_15"),
+ (ShiftJis, "SJIS"),
+ (Iso2022Jp, "ISO2022JP"),
+ (Iso2022Kr, "ISO2022KR"),
+ (Gb2312, "EUC_CN"),
+ (Utf8, "UTF8"),
+ (Utf16, "UTF_16"),
+ (Utf16, "UnicodeBig"),
+ (Utf16Be, "UnicodeBigUnmarked"),
+ (Utf16Le, "UnicodeLittleUnmarked"),
+ (Windows1252, "Cp1252")];
+
+/// Python codec names that are not IANA aliases, normalized like Python does.
+static PYTHON_NAMES: [(Charset, &str); 37] = [(UsAscii, "ascii"),
+ (UsAscii, "646"),
+ (Iso88591, "latin_1"),
+ (Iso88591, "latin"),
+ (Iso88591, "iso8859_1"),
+ (Iso88591, "8859"),
+ (Iso88592, "iso8859_2"),
+ (Iso88593, "iso8859_3"),
+ (Iso88594, "iso8859_4"),
+ (Iso88595, "iso8859_5"),
+ (Iso88596, "iso8859_6"),
+ (Iso88597, "iso8859_7"),
+ (Iso88598, "iso8859_8"),
+ (Iso88599, "iso8859_9"),
+ (Iso885910, "iso8859_10"),
+ (Iso885915, "iso8859_15"),
+ (ShiftJis, "sjis"),
+ (ShiftJis, "s_jis"),
+ (EucJp, "eucjp"),
+ (EucJp, "ujis"),
+ (EucJp, "u_jis"),
+ (EucKr, "euckr"),
+ (Iso2022Jp, "iso2022_jp"),
+ (Iso2022Jp, "iso2022jp"),
+ (Iso2022Jp2, "iso2022_jp_2"),
+ (Iso2022Kr, "iso2022_kr"),
+ (Iso2022Kr, "iso2022kr"),
+ (Gb2312, "euc_cn"),
+ (Gb2312, "eucgb2312_cn"),
+ (Big5, "big5_tw"),
+ (Utf8, "utf8"),
+ (Utf8, "u8"),
+ (Utf8, "utf"),
+ (Utf16, "utf16"),
+ (Utf16Be, "utf_16_be"),
+ (Utf16Le, "utf_16_le"),
+ (Windows1252, "cp1252")];
+
+/// Emacs coding system names that are not IANA aliases, without EOL suffix.
+static EMACS_NAMES: [(Charset, &str); 33] = [(Iso88591, "latin-1"),
+ (Iso88591, "iso-latin-1"),
+ (Iso88592, "latin-2"),
+ (Iso88592, "iso-latin-2"),
+ (Iso88593, "latin-3"),
+ (Iso88593, "iso-latin-3"),
+ (Iso88594, "latin-4"),
+ (Iso88594, "iso-latin-4"),
+ (Iso88595, "cyrillic-iso-8bit"),
+ (Iso88596, "arabic-iso-8bit"),
+ (Iso88597, "greek-iso-8bit"),
+ (Iso88598, "hebrew-iso-8bit"),
+ (Iso88599, "latin-5"),
+ (Iso88599, "iso-latin-5"),
+ (Iso885910, "latin-6"),
+ (Iso885910, "iso-latin-6"),
+ (Iso885915, "latin-9"),
+ (Iso885915, "iso-latin-9"),
+ (ShiftJis, "japanese-shift-jis"),
+ (ShiftJis, "sjis"),
+ (EucJp, "japanese-iso-8bit"),
+ (EucJp, "euc-japan"),
+ (Iso2022Jp, "junet"),
+ (Iso2022Jp, "japanese-iso-7bit-1978-irv"),
+ (EucKr, "korean-iso-8bit"),
+ (EucKr, "euc-korea"),
+ (Iso2022Kr, "korean-iso-7bit-lock"),
+ (Gb2312, "chinese-iso-8bit"),
+ (Gb2312, "euc-china"),
+ (Big5, "chinese-big5"),
+ (Gbk, "chinese-gbk"),
+ (Koi8R, "cyrillic-koi8"),
+ (Utf8, "mule-utf-8")];
+
+/// Windows code page numbers, the first entry of a charset is its preferred
+/// code page.
+static CODEPAGES: [(Charset, u16); 30] = [(UsAscii, 20127),
+ (Iso88591, 28591),
+ (Iso88592, 28592),
+ (Iso88593, 28593),
+ (Iso88594, 28594),
+ (Iso88595, 28595),
+ (Iso88596, 28596),
+ (Iso88597, 28597),
+ (Iso88598, 28598),
+ (Iso88599, 28599),
+ (Iso88598I, 38598),
+ (ShiftJis, 932),
+ (EucJp, 20932),
+ (EucJp, 51932),
+ (Iso2022Kr, 50225),
+ (EucKr, 51949),
+ (Iso2022Jp, 50220),
+ (Gb2312, 20936),
+ (Gbk, 936),
+ (Big5, 950),
+ (Koi8R, 20866),
+ (Koi8U, 21866),
+ (Utf8, 65001),
+ (Windows1252, 1252),
+ (Utf16Le, 1200),
+ (Utf16Be, 1201),
+ (Iso885915, 28605),
+ (Gb18030, 54936),
+ (Utf32Le, 12000),
+ (Utf32Be, 12001)];
+
+/// Names of unregistered charsets discouraged for new content.
+static DEPRECATED_NAMES: [&str; 4] = ["UTF-7", "HZ-GB-2312", "ISO-2022-CN", "ISO-2022-CN-EXT"];
+
+/// Charsets commonly used on the web, most recommended first.
+static COMMON_WEB_CHARSETS: [Charset; 10] = [Utf8,
+                                             Utf16,
+                                             Windows1252,
+                                             Iso88591,
+                                             Iso885915,
+                                             ShiftJis,
+                                             EucJp,
+                                             EucKr,
+                                             Gb18030,
+                                             Big5];
+
+/// Preferred MIME names for charsets where they differ from the canonical name.
+static MIME_NAMES: [(Charset, &str); 3] = [(ShiftJis, "Shift_JIS"),
+ (Utf8, "UTF-8"),
+ (Windows1252, "windows-1252")];
+
+/// Byte order marks, UTF-32LE before UTF-16LE as it starts with the same bytes.
+static BOMS: [(Charset, &[u8]); 5] = [(Utf8, b"\xef\xbb\xbf"),
+                                      (Utf32Be, b"\x00\x00\xfe\xff"),
+                                      (Utf32Le, b"\xff\xfe\x00\x00"),
+                                      (Utf16Be, b"\xfe\xff"),
+                                      (Utf16Le, b"\xff\xfe")];
 
 impl Charset {
     fn name(&self) -> &str {
-        if let &Unregistered(ref s) = self {
+        if let Unregistered(ref s) = *self {
             return &s[..];
         }
-        MAPPING.iter()
-               .find(|&&(ref variant, _)| self == variant)
-               .map(|&(_, name)| name)
-               .unwrap()
+        self.info().map(|info| info.name).unwrap()
     }
-}
 
-impl Display for Charset {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        f.write_str(self.name())
+    /// Returns the length of the canonical name in bytes.
+    pub const fn name_len(&self) -> usize {
+        match *self {
+            Unregistered(ref s) => s.len(),
+            ref registered => match registered.static_name() {
+                Some(name) => name.len(),
+                None => 0,
+            },
+        }
     }
-}
 
-impl FromStr for Charset {
-    type Err = ();
-    fn from_str(s: &str) -> Result<Charset, ()> {
-        Ok(MAPPING.iter()
-                  .find(|&&(_, ref name)| name.eq_ignore_ascii_case(s))
-                  .map(|&(ref variant, _)| variant.to_owned())
-                  .unwrap_or(Unregistered(s.to_owned())))
+    /// Returns the canonical name as static bytes, `None` for unregistered
+    /// charsets. Needs no allocation and works in const contexts.
+    pub const fn canonical_bytes(&self) -> Option<&'static [u8]> {
+        match self.static_name() {
+            Some(name) => Some(name.as_bytes()),
+            None => None,
+        }
     }
-}
 
-impl PartialEq for Charset {
-    fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (&UsAscii, &UsAscii) |
-            (&Iso88591, &Iso88591) |
-            (&Iso88592, &Iso88592) |
-            (&Iso88593, &Iso88593) |
-            (&Iso88594, &Iso88594) |
-            (&Iso88595, &Iso88595) |
-            (&Iso88596, &Iso88596) |
-            (&Iso88597, &Iso88597) |
-            (&Iso88598, &Iso88598) |
-            (&Iso88599, &Iso88599) |
-            (&Iso885910, &Iso885910) |
-            (&ShiftJis, &ShiftJis) |
-            (&EucJp, &EucJp) |
-            (&Iso2022Kr, &Iso2022Kr) |
-            (&EucKr, &EucKr) |
-            (&Iso2022Jp, &Iso2022Jp) |
-            (&Iso2022Jp2, &Iso2022Jp2) |
-            (&Iso88596E, &Iso88596E) |
-            (&Iso88596I, &Iso88596I) |
-            (&Iso88598E, &Iso88598E) |
-            (&Iso88598I, &Iso88598I) |
-            (&Gb2312, &Gb2312) |
-            (&Big5, &Big5) |
-            (&Koi8R, &Koi8R) |
-            (&Utf8, &Utf8) => true,
-            (&Unregistered(ref s), &Unregistered(ref t)) => s.eq_ignore_ascii_case(t),
+    /// Repeats the names of `REGISTRY` for the `const fn`s, which can not
+    /// search it.
+    const fn static_name(&self) -> Option<&'static str> {
+        match *self {
+            UsAscii => Some("US-ASCII"),
+            Iso88591 => Some("ISO-8859-1"),
+            Iso88592 => Some("ISO-8859-2"),
+            Iso88593 => Some("ISO-8859-3"),
+            Iso88594 => Some("ISO-8859-4"),
+            Iso88595 => Some("ISO-8859-5"),
+            Iso88596 => Some("ISO-8859-6"),
+            Iso88597 => Some("ISO-8859-7"),
+            Iso88598 => Some("ISO-8859-8"),
+            Iso88599 => Some("ISO-8859-9"),
+            Iso885910 => Some("ISO-8859-10"),
+            ShiftJis => Some("Shift-JIS"),
+            EucJp => Some("EUC-JP"),
+            Iso2022Kr => Some("ISO-2022-KR"),
+            EucKr => Some("EUC-KR"),
+            Iso2022Jp => Some("ISO-2022-JP"),
+            Iso2022Jp2 => Some("ISO-2022-JP-2"),
+            Iso88596E => Some("ISO-8859-6-E"),
+            Iso88596I => Some("ISO-8859-6-I"),
+            Iso88598E => Some("ISO-8859-8-E"),
+            Iso88598I => Some("ISO-8859-8-I"),
+            Gb2312 => Some("GB2312"),
+            Big5 => Some("Big5"),
+            Koi8R => Some("KOI8-R"),
+            Utf8 => Some("utf-8"),
+            Windows1252 => Some("WINDOWS-1252"),
+            Utf16Be => Some("UTF-16BE"),
+            Utf16Le => Some("UTF-16LE"),
+            Utf16 => Some("UTF-16"),
+            Koi8U => Some("KOI8-U"),
+            Gbk => Some("GBK"),
+            Iso885915 => Some("ISO-8859-15"),
+            Gb18030 => Some("GB18030"),
+            Utf32 => Some("UTF-32"),
+            Utf32Be => Some("UTF-32BE"),
+            Utf32Le => Some("UTF-32LE"),
+            Unregistered(_) => None,
+        }
+    }
+
+    fn info(&self) -> Option<&'static CharsetInfo> {
+        REGISTRY.iter().find(|info| info.charset == *self)
+    }
+
+    /// Returns the table of all registered charsets with their names, aliases
+    /// and MIBenums.
+    pub fn registry() -> &'static [CharsetInfo] {
+        &REGISTRY
+    }
+
+    /// Returns the canonical name as bytes, for comparing against raw header
+    /// values.
+    pub fn as_bytes_name(&self) -> &[u8] {
+        self.name().as_bytes()
+    }
+
+    /// Returns the canonical name in upper case, borrowed when it already is.
+    pub fn upper_name(&self) -> Cow<'_, str> {
+        let name = self.name();
+        if name.bytes().any(|b| b.is_ascii_lowercase()) {
+            Cow::Owned(name.to_ascii_uppercase())
+        } else {
+            Cow::Borrowed(name)
+        }
+    }
+
+    fn preferred_mime_name(&self) -> &str {
+        MIME_NAMES.iter()
+                  .find(|&(variant, _)| self == variant)
+                  .map_or_else(|| self.name(), |&(_, name)| name)
+    }
+
+    /// Returns all registered charsets.
+    pub fn all() -> impl Iterator<Item = Charset> {
+        REGISTRY.iter().map(|info| info.charset.clone())
+    }
+
+    /// Returns all registered charsets paired with their MIBenum.
+    pub fn all_with_mibenum() -> impl Iterator<Item = (Charset, u16)> {
+        REGISTRY.iter().map(|info| (info.charset.clone(), info.mibenum))
+    }
+
+    /// Returns the number of registered charsets.
+    pub fn registered_count() -> usize {
+        REGISTRY.len()
+    }
+
+    /// Returns the registered charset at position `i` of the registry, `None`
+    /// if `i` is out of range.
+    pub fn from_registry_index(i: usize) -> Option<Charset> {
+        REGISTRY.get(i).map(|info| info.charset.clone())
+    }
+
+    /// Returns a short list of charsets commonly used on the web, starting
+    /// with the recommended `UTF-8`.
+    pub fn common_web_charsets() -> &'static [Charset] {
+        &COMMON_WEB_CHARSETS
+    }
+
+    /// Returns the MIBenum assigned by IANA, `None` for unregistered charsets.
+    pub fn mibenum(&self) -> Option<u16> {
+        self.info().map(|info| info.mibenum)
+    }
+
+    /// Returns a `Copy` handle to this charset, `None` for unregistered
+    /// charsets.
+    pub fn code(&self) -> Option<CharsetCode> {
+        self.mibenum().map(CharsetCode)
+    }
+
+    /// Returns the MIBenum in network byte order, as encoded by SNMP.
+    pub fn mibenum_be_bytes(&self) -> Option<[u8; 2]> {
+        self.mibenum().map(u16::to_be_bytes)
+    }
+
+    /// Returns the registered charset with the MIBenum in network byte order.
+    pub fn from_mibenum_be_bytes(bytes: [u8; 2]) -> Option<Charset> {
+        CharsetCode(u16::from_be_bytes(bytes)).charset()
+    }
+
+    /// Compares two charsets by their MIBenum, unregistered charsets sort last.
+    ///
+    /// Unlike `Ord` all unregistered charsets compare equal.
+    pub fn cmp_by_mibenum(&self, other: &Charset) -> Ordering {
+        match (self.mibenum(), other.mibenum()) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    }
+
+    /// Converts a Windows code page number, like 1252 or 65001 for UTF-8.
+    ///
+    /// Returns `None` for unknown code pages.
+    pub fn from_codepage(cp: u16) -> Option<Charset> {
+        CODEPAGES.iter()
+                 .find(|&&(_, codepage)| codepage == cp)
+                 .map(|(variant, _)| variant.clone())
+    }
+
+    /// Returns the Windows code page number, `None` if there is none.
+    pub fn codepage(&self) -> Option<u16> {
+        CODEPAGES.iter()
+                 .find(|&(variant, _)| self == variant)
+                 .map(|&(_, codepage)| codepage)
+    }
+
+    /// Returns all labels of the charset, the canonical name followed by the
+    /// aliases.
+    pub fn labels<'a>(&'a self) -> impl Iterator<Item = &'a str> + 'a {
+        iter::once(self.name()).chain(self.aliases().iter().cloned())
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        self.info().map_or(&[], |info| info.aliases)
+    }
+
+    /// Returns the number of aliases, not counting the canonical name.
+    pub fn alias_count(&self) -> usize {
+        self.aliases().len()
+    }
+
+    /// Returns true if `label` is an alias of this charset, ignoring case.
+    ///
+    /// The canonical name is not an alias.
+    pub fn has_alias(&self, label: &str) -> bool {
+        self.aliases().iter().any(|alias| alias.eq_ignore_ascii_case(label))
+    }
+
+    /// Returns true if `label` is the canonical name or an alias of this
+    /// charset, ignoring ASCII case. Does not allocate.
+    pub fn matches_label_bytes(&self, label: &[u8]) -> bool {
+        self.labels().any(|candidate| candidate.as_bytes().eq_ignore_ascii_case(label))
+    }
+
+    /// Returns the byte conventionally substituted for unmappable characters
+    /// when encoding to a single-byte charset.
+    ///
+    /// Returns `None` for Unicode, multi-byte, stateful and unregistered
+    /// charsets.
+    pub fn replacement_char(&self) -> Option<u8> {
+        match *self {
+            UsAscii | Iso88591 | Iso88592 | Iso88593 | Iso88594 | Iso88595 | Iso88596 |
+            Iso88597 | Iso88598 | Iso88599 | Iso885910 | Iso88596E | Iso88596I | Iso88598E |
+            Iso88598I | Koi8R | Windows1252 | Koi8U | Iso885915 => Some(b'?'),
+            ShiftJis | EucJp | Iso2022Kr | EucKr | Iso2022Jp | Iso2022Jp2 | Gb2312 | Big5 |
+            Utf8 | Utf16Be | Utf16Le | Utf16 | Gbk | Gb18030 | Utf32 | Utf32Be | Utf32Le |
+            Unregistered(_) => None,
+        }
+    }
+
+    /// Returns a human-readable name for user interfaces, like
+    /// "Western European (ISO-8859-1)".
+    pub fn display_name(&self) -> &str {
+        match *self {
+            UsAscii => "English (US-ASCII)",
+            Iso88591 => "Western European (ISO-8859-1)",
+            Iso88592 => "Central European (ISO-8859-2)",
+            Iso88593 => "South European (ISO-8859-3)",
+            Iso88594 => "Baltic (ISO-8859-4)",
+            Iso88595 => "Cyrillic (ISO-8859-5)",
+            Iso88596 => "Arabic (ISO-8859-6)",
+            Iso88597 => "Greek (ISO-8859-7)",
+            Iso88598 => "Hebrew, Visual (ISO-8859-8)",
+            Iso88599 => "Turkish (ISO-8859-9)",
+            Iso885910 => "Nordic (ISO-8859-10)",
+            ShiftJis => "Japanese (Shift_JIS)",
+            EucJp => "Japanese (EUC-JP)",
+            Iso2022Kr => "Korean (ISO-2022-KR)",
+            EucKr => "Korean (EUC-KR)",
+            Iso2022Jp => "Japanese (ISO-2022-JP)",
+            Iso2022Jp2 => "Multilingual (ISO-2022-JP-2)",
+            Iso88596E => "Arabic, Explicit (ISO-8859-6-E)",
+            Iso88596I => "Arabic, Implicit (ISO-8859-6-I)",
+            Iso88598E => "Hebrew, Explicit (ISO-8859-8-E)",
+            Iso88598I => "Hebrew, Logical (ISO-8859-8-I)",
+            Gb2312 => "Chinese Simplified (GB2312)",
+            Big5 => "Chinese Traditional (Big5)",
+            Koi8R => "Russian (KOI8-R)",
+            Utf8 => "Unicode (UTF-8)",
+            Windows1252 => "Western European (Windows-1252)",
+            Utf16Be => "Unicode (UTF-16BE)",
+            Utf16Le => "Unicode (UTF-16LE)",
+            Utf16 => "Unicode (UTF-16)",
+            Koi8U => "Ukrainian (KOI8-U)",
+            Gbk => "Chinese Simplified (GBK)",
+            Iso885915 => "Western European (ISO-8859-15)",
+            Gb18030 => "Chinese Simplified (GB18030)",
+            Utf32 => "Unicode (UTF-32)",
+            Utf32Be => "Unicode (UTF-32BE)",
+            Utf32Le => "Unicode (UTF-32LE)",
+            Unregistered(ref s) => s,
+        }
+    }
+
+    /// Returns the identifier of the enum variant, like `"Iso88591"`, for
+    /// generating Rust code. Returns `None` for unregistered charsets.
+    pub fn enum_ident(&self) -> Option<&'static str> {
+        match *self {
+            UsAscii => Some("UsAscii"),
+            Iso88591 => Some("Iso88591"),
+            Iso88592 => Some("Iso88592"),
+            Iso88593 => Some("Iso88593"),
+            Iso88594 => Some("Iso88594"),
+            Iso88595 => Some("Iso88595"),
+            Iso88596 => Some("Iso88596"),
+            Iso88597 => Some("Iso88597"),
+            Iso88598 => Some("Iso88598"),
+            Iso88599 => Some("Iso88599"),
+            Iso885910 => Some("Iso885910"),
+            ShiftJis => Some("ShiftJis"),
+            EucJp => Some("EucJp"),
+            Iso2022Kr => Some("Iso2022Kr"),
+            EucKr => Some("EucKr"),
+            Iso2022Jp => Some("Iso2022Jp"),
+            Iso2022Jp2 => Some("Iso2022Jp2"),
+            Iso88596E => Some("Iso88596E"),
+            Iso88596I => Some("Iso88596I"),
+            Iso88598E => Some("Iso88598E"),
+            Iso88598I => Some("Iso88598I"),
+            Gb2312 => Some("Gb2312"),
+            Big5 => Some("Big5"),
+            Koi8R => Some("Koi8R"),
+            Utf8 => Some("Utf8"),
+            Windows1252 => Some("Windows1252"),
+            Utf16Be => Some("Utf16Be"),
+            Utf16Le => Some("Utf16Le"),
+            Utf16 => Some("Utf16"),
+            Koi8U => Some("Koi8U"),
+            Gbk => Some("Gbk"),
+            Iso885915 => Some("Iso885915"),
+            Gb18030 => Some("Gb18030"),
+            Utf32 => Some("Utf32"),
+            Utf32Be => Some("Utf32Be"),
+            Utf32Le => Some("Utf32Le"),
+            Unregistered(_) => None,
+        }
+    }
+
+    /// Returns the family this charset belongs to.
+    pub fn group(&self) -> CharsetGroup {
+        match *self {
+            Utf8 | Utf16Be | Utf16Le | Utf16 | Utf32 | Utf32Be | Utf32Le => CharsetGroup::Unicode,
+            UsAscii | Iso88591 | Iso88592 | Iso88593 | Iso88594 | Iso88599 | Iso885910 |
+            Windows1252 | Iso885915 => CharsetGroup::Western,
+            Iso88595 | Koi8R | Koi8U => CharsetGroup::Cyrillic,
+            ShiftJis | EucJp | Iso2022Kr | EucKr | Iso2022Jp | Iso2022Jp2 | Gb2312 | Big5 |
+            Gbk | Gb18030 => CharsetGroup::Cjk,
+            Iso88596 | Iso88596E | Iso88596I => CharsetGroup::Arabic,
+            Iso88598 | Iso88598E | Iso88598I => CharsetGroup::Hebrew,
+            Iso88597 => CharsetGroup::Greek,
+            Unregistered(_) => CharsetGroup::Other,
+        }
+    }
+
+    /// Returns a key to sort charsets by their `group` in declaration order
+    /// and then by canonical name.
+    ///
+    /// The key borrows the charset, so compare keys with `sort_by` as
+    /// `sort_by_key` can not return borrowed keys.
+    pub fn grouped_sort_key(&self) -> (u8, &str) {
+        (self.group() as u8, self.name())
+    }
+
+    /// Returns the maximum number of bytes needed to encode a single
+    /// character, `None` for unregistered charsets.
+    ///
+    /// For the stateful ISO-2022 charsets this includes the escape sequences
+    /// to switch to the character set and back.
+    pub fn max_bytes_per_char(&self) -> Option<usize> {
+        match *self {
+            UsAscii | Iso88591 | Iso88592 | Iso88593 | Iso88594 | Iso88595 | Iso88596 |
+            Iso88597 | Iso88598 | Iso88599 | Iso885910 | Iso88596E | Iso88596I | Iso88598E |
+            Iso88598I | Koi8R | Windows1252 | Koi8U | Iso885915 => Some(1),
+            ShiftJis | EucKr | Gb2312 | Big5 | Gbk => Some(2),
+            EucJp => Some(3),
+            Utf8 | Utf16Be | Utf16Le | Utf16 | Gb18030 | Utf32 | Utf32Be | Utf32Le => Some(4),
+            Iso2022Kr | Iso2022Jp => Some(8),
+            Iso2022Jp2 => Some(9),
+            Unregistered(_) => None,
+        }
+    }
+
+    /// Returns an upper bound of the number of bytes needed to encode `text`
+    /// in this charset, without a byte order mark.
+    ///
+    /// This is the number of characters times `max_bytes_per_char`,
+    /// saturating on overflow. Returns `None` for unregistered charsets.
+    pub fn estimate_encoded_len(&self, text: &str) -> Option<usize> {
+        self.max_bytes_per_char().map(|max| text.chars().count().saturating_mul(max))
+    }
+
+    /// Returns true if decoding valid text to Unicode and encoding it again
+    /// is guaranteed to give back the same bytes.
+    ///
+    /// This is conservative: it is true for the Unicode and single-byte
+    /// charsets and `GB18030`, but false for the other multi-byte charsets,
+    /// which have duplicate mappings or, for ISO-2022, several ways to
+    /// encode the same text, and for unregistered charsets.
+    pub fn roundtrips_through_unicode(&self) -> bool {
+        match *self {
+            UsAscii | Iso88591 | Iso88592 | Iso88593 | Iso88594 | Iso88595 | Iso88596 |
+            Iso88597 | Iso88598 | Iso88599 | Iso885910 | Iso88596E | Iso88596I | Iso88598E |
+            Iso88598I | Koi8R | Utf8 | Windows1252 | Utf16Be | Utf16Le | Utf16 | Koi8U |
+            Iso885915 | Gb18030 | Utf32 | Utf32Be | Utf32Le => true,
+            ShiftJis | EucJp | Iso2022Kr | EucKr | Iso2022Jp | Iso2022Jp2 | Gb2312 | Big5 |
+            Gbk | Unregistered(_) => false,
+        }
+    }
+
+    /// Returns true if ASCII bytes mean the same in this charset as in UTF-8,
+    /// so pure ASCII input can be passed through without decoding.
+    ///
+    /// This is false for stateful ISO-2022 charsets, where escape sequences
+    /// change the meaning of ASCII bytes, and for unregistered charsets.
+    pub fn ascii_passthrough(&self) -> bool {
+        match *self {
+            UsAscii | Iso88591 | Iso88592 | Iso88593 | Iso88594 | Iso88595 | Iso88596 |
+            Iso88597 | Iso88598 | Iso88599 | Iso885910 | ShiftJis | EucJp | EucKr |
+            Iso88596E | Iso88596I | Iso88598E | Iso88598I | Gb2312 | Big5 | Koi8R | Utf8 |
+            Windows1252 | Koi8U | Gbk | Iso885915 | Gb18030 => true,
+            Iso2022Kr | Iso2022Jp | Iso2022Jp2 | Utf16Be | Utf16Le | Utf16 | Utf32 | Utf32Be |
+            Utf32Le | Unregistered(_) => false,
+        }
+    }
+
+    /// Returns all registered charsets with a name or alias starting with
+    /// `prefix`, ignoring case.
+    pub fn variants_matching(prefix: &str) -> Vec<Charset> {
+        Charset::all()
+            .filter(|charset| {
+                charset.labels().any(|label| starts_with_ignore_ascii_case(label, prefix))
+            })
+            .collect()
+    }
+
+    /// Returns true if the charset is discouraged for new content.
+    ///
+    /// These are the stateful ISO-2022 charsets and `UTF-7`, whose escape
+    /// sequences can hide content from filters, and the charsets the WHATWG
+    /// Encoding Standard maps to the `replacement` encoding.
+    pub fn is_deprecated(&self) -> bool {
+        match *self {
+            Iso2022Kr | Iso2022Jp | Iso2022Jp2 => true,
+            Unregistered(ref s) => {
+                DEPRECATED_NAMES.iter().any(|name| name.eq_ignore_ascii_case(s))
+            }
             _ => false,
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Returns true if ASCII text needs no conversion between the two
+    /// charsets, because both are ASCII compatible.
+    ///
+    /// This is a pragmatic check based on `ascii_passthrough`, it does not
+    /// compare the repertoires beyond ASCII.
+    pub fn ascii_superset_of(&self, other: &Charset) -> bool {
+        self.ascii_passthrough() && other.ascii_passthrough()
+    }
 
-    #[test]
-    fn test_parse() {
-        assert_eq!(UsAscii, "us-ascii".parse().unwrap());
-        assert_eq!(UsAscii, "US-Ascii".parse().unwrap());
-        assert_eq!(UsAscii, "US-ASCII".parse().unwrap());
-        assert_eq!(ShiftJis, "Shift-JIS".parse().unwrap());
-        assert_eq!(Unregistered("ABCD".to_owned()), "abcd".parse().unwrap());
+    /// Converts this charset into an `Unregistered` value holding the
+    /// canonical name, the reverse of `canonicalize`.
+    pub fn into_unregistered(self) -> Charset {
+        match self {
+            Unregistered(_) => self,
+            registered => Unregistered(registered.name().to_owned()),
+        }
     }
 
-    #[test]
-    fn test_display() {
-        assert_eq!("US-ASCII", UsAscii.to_string());
-        assert_eq!("ABCD", Unregistered("ABCD".to_owned()).to_string());
+    /// Converts the name of an unregistered charset to upper case, as the
+    /// type documentation describes. Registered charsets are returned
+    /// unchanged.
+    ///
+    /// `FromStr` keeps unregistered names as they are, use this to opt in.
+    pub fn with_normalized_case(self) -> Charset {
+        match self {
+            Unregistered(s) => Unregistered(s.to_ascii_uppercase()),
+            registered => registered,
+        }
     }
 
-    #[test]
-    fn test_cmp() {
-        assert!(Iso88593 == Iso88593);
-        assert!(UsAscii != Iso88593);
-        assert_eq!(Unregistered("foobar".to_owned()),
-                   Unregistered("FOOBAR".to_owned()));
+    /// Returns this charset if it is registered and `fallback` otherwise.
+    pub fn or_registered(self, fallback: Charset) -> Charset {
+        match self {
+            Unregistered(_) => fallback,
+            registered => registered,
+        }
+    }
+
+    /// Resolves an unregistered charset named like a registered charset or
+    /// one of its aliases to the registered variant.
+    pub fn canonicalize(&self) -> Charset {
+        match *self {
+            Unregistered(ref s) => Charset::from_label(s),
+            ref other => other.clone(),
+        }
+    }
+
+    /// Compares two charsets after resolving aliases with `canonicalize`.
+    ///
+    /// Unlike `==` this considers `Iso88591` and `Unregistered("latin1")` equal.
+    pub fn canonical_eq(&self, other: &Charset) -> bool {
+        self.canonicalize() == other.canonicalize()
+    }
+
+    /// Compares two charsets ignoring the `-E` and `-I` suffixes of the
+    /// bidirectional Arabic and Hebrew variants.
+    pub fn eq_ignore_bidi(&self, other: &Charset) -> bool {
+        self.base_charset() == other.base_charset()
+    }
+
+    /// Returns true if both charsets denote the same underlying encoding.
+    ///
+    /// Unregistered charsets named like a registered charset or one of its
+    /// aliases are resolved with `canonicalize` first, then the charsets are
+    /// compared with `eq_ignore_bidi`.
+    pub fn same_encoding(&self, other: &Charset) -> bool {
+        self.canonicalize().eq_ignore_bidi(&other.canonicalize())
+    }
+
+    fn base_charset(&self) -> &Charset {
+        match *self {
+            Iso88596E | Iso88596I => &Iso88596,
+            Iso88598E | Iso88598I => &Iso88598,
+            ref other => other,
+        }
+    }
+
+    /// Returns true if text in this charset must be labeled explicitly.
+    ///
+    /// Only `UTF-8` and `US-ASCII` are implied defaults in modern specs: MIME
+    /// `text/*` types default to `US-ASCII` (RFC 2046, RFC 6657) and newer
+    /// formats like JSON (RFC 8259) default to `UTF-8`.
+    pub fn requires_explicit_declaration(&self) -> bool {
+        !matches!(*self, Utf8 | UsAscii)
+    }
+
+    /// Returns true if the charset can represent all Unicode characters.
+    ///
+    /// These are the UTF charsets and `GB18030`, legacy charsets only cover
+    /// a subset.
+    pub fn covers_unicode(&self) -> bool {
+        matches!(*self,
+                 Utf8 | Utf16Be | Utf16Le | Utf16 | Utf32 | Utf32Be | Utf32Le | Gb18030)
+    }
+
+    /// Returns true if the same bytes always decode to the same text.
+    ///
+    /// This is false for `UTF-16` and `UTF-32`, whose byte order depends on a
+    /// BOM, and true for charsets with an explicit byte order and byte
+    /// oriented charsets.
+    pub fn byte_order_independent(&self) -> bool {
+        !matches!(*self, Utf16 | Utf32)
+    }
+
+    /// Returns true for `Utf8`.
+    ///
+    /// UTF-8 input only needs to be validated and can be borrowed instead of
+    /// decoded into a new string:
+    ///
+    /// ```
+    /// use charsets::Charset;
+    ///
+    /// fn borrow_text<'a>(charset: &Charset, body: &'a [u8]) -> Option<&'a str> {
+    ///     if charset.is_utf8() {
+    ///         std::str::from_utf8(body).ok()
+    ///     } else {
+    ///         None
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(Some("caf\u{e9}"), borrow_text(&Charset::Utf8, b"caf\xc3\xa9"));
+    /// assert_eq!(None, borrow_text(&Charset::Iso88591, b"caf\xe9"));
+    /// ```
+    pub fn is_utf8(&self) -> bool {
+        *self == Utf8
+    }
+
+    /// Returns true for unregistered charsets named with the private use
+    /// prefix `x-`, ignoring case.
+    pub fn is_private_use(&self) -> bool {
+        match *self {
+            Unregistered(ref s) => starts_with_ignore_ascii_case(s, "x-"),
+            _ => false,
+        }
+    }
+
+    /// Returns true if this is the recommended charset for new web content.
+    ///
+    /// The HTML standard requires `UTF-8` for new documents, so this is only
+    /// true for `Utf8`.
+    pub fn is_web_safe_default(&self) -> bool {
+        *self == Utf8
+    }
+
+    /// Returns true if checking input for UTF-8 validity is meaningful before
+    /// falling back to this charset.
+    ///
+    /// This is false for UTF-16 and UTF-32, whose code units are not byte
+    /// oriented.
+    pub fn utf8_detection_applicable(&self) -> bool {
+        !matches!(*self, Utf16Be | Utf16Le | Utf16 | Utf32 | Utf32Be | Utf32Le)
+    }
+
+    /// Returns the charset web browsers use to decode content labeled with
+    /// this charset.
+    ///
+    /// Following the WHATWG Encoding Standard `ISO-8859-1` and `US-ASCII` are
+    /// decoded as `windows-1252`, all other charsets are returned unchanged.
+    pub fn web_decoding_charset(&self) -> Charset {
+        match *self {
+            UsAscii | Iso88591 => Windows1252,
+            ref other => other.clone(),
+        }
+    }
+
+    /// Returns true if `label` means a different encoding to IANA than to the
+    /// WHATWG Encoding Standard used by web browsers.
+    ///
+    /// These are the labels of:
+    ///
+    /// * `US-ASCII` and `ISO-8859-1`, decoded as `windows-1252`
+    /// * `ISO-8859-9`, decoded as `windows-1254`
+    /// * `GB2312`, decoded as `GBK`
+    /// * `ISO-2022-KR`, decoded with the WHATWG `replacement` decoder
+    /// * `UTF-16`, decoded as `UTF-16LE` instead of big endian without a BOM
+    ///
+    /// The last two have no equivalent WHATWG encoding, so `whatwg_name`
+    /// returns `None` for them.
+    pub fn is_ambiguous_label(label: &str) -> bool {
+        matches!(Charset::intern(label.trim()),
+                 Some(&UsAscii) | Some(&Iso88591) | Some(&Iso88599) | Some(&Gb2312) |
+                 Some(&Iso2022Kr) | Some(&Utf16))
+    }
+
+    /// Returns the lower-case name of the equivalent encoding in the WHATWG
+    /// Encoding Standard.
+    ///
+    /// This can differ from the IANA name, for example `ISO-8859-1` maps to
+    /// `windows-1252`. Returns `None` if there is no equivalent encoding,
+    /// including for `ISO-2022-KR` and `UTF-16` whose WHATWG labels mean a
+    /// different encoding.
+    pub fn whatwg_name(&self) -> Option<&'static str> {
+        match *self {
+            UsAscii | Iso88591 | Windows1252 => Some("windows-1252"),
+            Iso88592 => Some("iso-8859-2"),
+            Iso88593 => Some("iso-8859-3"),
+            Iso88594 => Some("iso-8859-4"),
+            Iso88595 => Some("iso-8859-5"),
+            Iso88596 | Iso88596E | Iso88596I => Some("iso-8859-6"),
+            Iso88597 => Some("iso-8859-7"),
+            Iso88598 | Iso88598E => Some("iso-8859-8"),
+            Iso88598I => Some("iso-8859-8-i"),
+            Iso88599 => Some("windows-1254"),
+            Iso885910 => Some("iso-8859-10"),
+            ShiftJis => Some("shift_jis"),
+            EucJp => Some("euc-jp"),
+            EucKr => Some("euc-kr"),
+            Iso2022Jp => Some("iso-2022-jp"),
+            Gb2312 => Some("gbk"),
+            Big5 => Some("big5"),
+            Koi8R => Some("koi8-r"),
+            Utf8 => Some("utf-8"),
+            Utf16Be => Some("utf-16be"),
+            Utf16Le => Some("utf-16le"),
+            Koi8U => Some("koi8-u"),
+            Gbk => Some("gbk"),
+            Iso885915 => Some("iso-8859-15"),
+            Gb18030 => Some("gb18030"),
+            Iso2022Kr | Iso2022Jp2 | Utf16 | Utf32 | Utf32Be | Utf32Le | Unregistered(_) => None,
+        }
+    }
+
+    /// Returns the `encoding_rs` encoding for the charset.
+    ///
+    /// Like in web browsers `US-ASCII` and `ISO-8859-1` map to `windows-1252`,
+    /// which decodes 0x80 to 0x9F as printable characters and `US-ASCII` bytes
+    /// above 0x7F as Latin-1. The decoders and encoders of this crate handle
+    /// both charsets themselves instead. Returns `None` if `encoding_rs` does
+    /// not support the charset.
+    #[cfg(feature = "encoding_rs")]
+    pub fn to_encoding_rs(&self) -> Option<&'static Encoding> {
+        match *self {
+            UsAscii | Iso88591 | Windows1252 => Some(encoding_rs::WINDOWS_1252),
+            Iso88592 => Some(encoding_rs::ISO_8859_2),
+            Iso88593 => Some(encoding_rs::ISO_8859_3),
+            Iso88594 => Some(encoding_rs::ISO_8859_4),
+            Iso88595 => Some(encoding_rs::ISO_8859_5),
+            Iso88596 | Iso88596E | Iso88596I => Some(encoding_rs::ISO_8859_6),
+            Iso88597 => Some(encoding_rs::ISO_8859_7),
+            Iso88598 | Iso88598E => Some(encoding_rs::ISO_8859_8),
+            Iso88598I => Some(encoding_rs::ISO_8859_8_I),
+            Iso88599 => Some(encoding_rs::WINDOWS_1254),
+            Iso885910 => Some(encoding_rs::ISO_8859_10),
+            ShiftJis => Some(encoding_rs::SHIFT_JIS),
+            EucJp => Some(encoding_rs::EUC_JP),
+            EucKr => Some(encoding_rs::EUC_KR),
+            Iso2022Jp => Some(encoding_rs::ISO_2022_JP),
+            Gb2312 | Gbk => Some(encoding_rs::GBK),
+            Big5 => Some(encoding_rs::BIG5),
+            Koi8R => Some(encoding_rs::KOI8_R),
+            Utf8 => Some(encoding_rs::UTF_8),
+            Utf16Be | Utf16 => Some(encoding_rs::UTF_16BE),
+            Utf16Le => Some(encoding_rs::UTF_16LE),
+            Koi8U => Some(encoding_rs::KOI8_U),
+            Iso885915 => Some(encoding_rs::ISO_8859_15),
+            Gb18030 => Some(encoding_rs::GB18030),
+            Iso2022Kr | Iso2022Jp2 | Utf32 | Utf32Be | Utf32Le => None,
+            Unregistered(ref s) => {
+                Encoding::for_label(s.as_bytes()).filter(|&e| e != encoding_rs::REPLACEMENT)
+            }
+        }
+    }
+
+    /// Returns the name of the `encoding_rs` encoding for the charset, like
+    /// `"windows-1252"` for `ISO-8859-1`.
+    ///
+    /// This matches `whatwg_name` ignoring case for all charsets with a WHATWG
+    /// equivalent.
+    #[cfg(feature = "encoding_rs")]
+    pub fn encoding_rs_name(&self) -> Option<&'static str> {
+        self.to_encoding_rs().map(Encoding::name)
+    }
+
+    /// Decodes bytes, replacing malformed sequences with U+FFFD.
+    ///
+    /// `US-ASCII` replaces every byte above 0x7F and `ISO-8859-1` maps every
+    /// byte to the code point of the same value, so 0x80 to 0x9F are C1
+    /// controls. Use `web_decoding_charset` first to decode them as
+    /// `windows-1252` like web browsers do.
+    ///
+    /// A byte order mark is only treated specially for `UTF-16`, where it
+    /// selects the byte order and is removed. Without a BOM `UTF-16` is
+    /// decoded as big endian. Returns `None` if there is no decoder for the
+    /// charset.
+    #[cfg(feature = "encoding_rs")]
+    pub fn decode<'a>(&self, bytes: &'a [u8]) -> Option<Cow<'a, str>> {
+        if let Some(max) = self.latin1_max() {
+            return Some(decode_latin1(bytes, max));
+        }
+        self.decoder_input(bytes).map(|(e, bytes)| e.decode_without_bom_handling(bytes).0)
+    }
+
+    /// Decodes bytes, letting a byte order mark override this charset.
+    ///
+    /// Like in web browsers a UTF-8 or UTF-16 BOM takes precedence over the
+    /// declared charset, the BOM is removed and the rest decoded in the
+    /// charset it indicates. UTF-32 BOMs are not recognized, so UTF-16LE text
+    /// starting with U+0000 is not mistaken for UTF-32LE. Without a BOM this
+    /// is the same as `decode`.
+    #[cfg(feature = "encoding_rs")]
+    pub fn decode_bom_aware<'a>(&self, bytes: &'a [u8]) -> Option<Cow<'a, str>> {
+        match Encoding::for_bom(bytes) {
+            Some((e, len)) => Some(e.decode_without_bom_handling(&bytes[len..]).0),
+            None => self.decode(bytes),
+        }
+    }
+
+    /// Decodes bytes like `decode`, but borrows pure ASCII input without
+    /// invoking a decoder if the charset is ASCII compatible.
+    #[cfg(feature = "encoding_rs")]
+    pub fn decode_fast<'a>(&self, bytes: &'a [u8]) -> Option<Cow<'a, str>> {
+        if self.ascii_passthrough() && bytes.is_ascii() {
+            if let Ok(s) = str::from_utf8(bytes) {
+                return Some(Cow::Borrowed(s));
+            }
+        }
+        self.decode(bytes)
+    }
+
+    /// Decodes bytes, failing on malformed sequences.
+    ///
+    /// Byte order marks, `US-ASCII` and `ISO-8859-1` are handled like in
+    /// `decode`, so any byte above 0x7F is an error for `US-ASCII` while
+    /// `ISO-8859-1` never fails. Returns `Error::Decode` if the input is
+    /// malformed or there is no decoder for the charset.
+    #[cfg(feature = "encoding_rs")]
+    pub fn decode_strict<'a>(&self, bytes: &'a [u8]) -> Result<Cow<'a, str>> {
+        let decoded = if let Some(max) = self.latin1_max() {
+            Some(decode_latin1(bytes, max)).filter(|s| !s.contains('\u{fffd}'))
+        } else {
+            self.decoder_input(bytes)
+                .and_then(|(e, bytes)| {
+                    e.decode_without_bom_handling_and_without_replacement(bytes)
+                })
+        };
+        decoded.ok_or_else(|| Error::Decode(DecodeError { charset: self.clone() }))
+    }
+
+    /// Returns a decoder for input split into chunks.
+    ///
+    /// Byte order marks are handled like in `decode`. Returns `None` if there
+    /// is no decoder for the charset.
+    #[cfg(feature = "encoding_rs")]
+    pub fn new_decoder(&self) -> Option<Decoder> {
+        if let Some(max) = self.latin1_max() {
+            return Some(Decoder { inner: None, pending: Vec::new(), max });
+        }
+        if *self == Utf16 {
+            return Some(Decoder { inner: None, pending: Vec::new(), max: char::MAX });
+        }
+        self.to_encoding_rs().map(|e| {
+            Decoder {
+                inner: Some(e.new_decoder_without_bom_handling()),
+                pending: Vec::new(),
+                max: char::MAX,
+            }
+        })
+    }
+
+    /// Returns an encoder for input split into chunks.
+    ///
+    /// Like decoding, `US-ASCII` and `ISO-8859-1` are encoded with their own
+    /// repertoire and not as `windows-1252`. Returns `None` if there is no
+    /// encoder for the charset, this includes the UTF-16 charsets.
+    #[cfg(feature = "encoding_rs")]
+    pub fn new_encoder(&self) -> Option<Encoder> {
+        if let Some(max) = self.latin1_max() {
+            return Some(Encoder { inner: None, max });
+        }
+        self.to_encoding_rs()
+            .filter(|&e| e.output_encoding() == e)
+            .map(|e| Encoder { inner: Some(e.new_encoder()), max: char::MAX })
+    }
+
+    /// Returns true if `text` can be encoded in this charset without replacing
+    /// unmappable characters.
+    ///
+    /// This agrees with the replacements reported by `new_encoder`. Returns
+    /// `None` if there is no encoder for the charset.
+    #[cfg(feature = "encoding_rs")]
+    pub fn can_encode_lossless(&self, text: &str) -> Option<bool> {
+        self.new_encoder().map(|mut encoder| !encoder.encode_chunk(text, true).1)
+    }
+
+    /// Recommends the first of `US-ASCII`, `ISO-8859-1`, `windows-1252` and
+    /// `ISO-8859-15` that can encode `text` without loss, or `UTF-8`.
+    #[cfg(feature = "encoding_rs")]
+    pub fn recommended_for(text: &str) -> Charset {
+        let candidates = [UsAscii, Iso88591, Windows1252, Iso885915];
+        candidates.iter()
+                  .find(|charset| charset.can_encode_lossless(text) == Some(true))
+                  .cloned()
+                  .unwrap_or(Utf8)
+    }
+
+    /// Returns the highest code point of charsets that map every byte up to
+    /// it to the code point of the same value.
+    #[cfg(feature = "encoding_rs")]
+    fn latin1_max(&self) -> Option<char> {
+        match *self {
+            UsAscii => Some('\u{7f}'),
+            Iso88591 => Some('\u{ff}'),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "encoding_rs")]
+    fn decoder_input<'a>(&self, bytes: &'a [u8]) -> Option<(&'static Encoding, &'a [u8])> {
+        if *self == Utf16 {
+            return Some(match Encoding::for_bom(bytes) {
+                Some((e, len)) if e != encoding_rs::UTF_8 => (e, &bytes[len..]),
+                _ => (encoding_rs::UTF_16BE, bytes),
+            });
+        }
+        self.to_encoding_rs().map(|e| (e, bytes))
+    }
+
+    /// Extracts the charset declared by a `<meta>` tag in the start of an HTML
+    /// document.
+    ///
+    /// Both `<meta charset="...">` and `<meta http-equiv="Content-Type"
+    /// content="...; charset=...">` are recognized. Only the first 1024 bytes
+    /// are examined. This is a heuristic and much simpler than the prescan
+    /// algorithm of the HTML standard.
+    pub fn parse_meta_charset(prefix: &[u8]) -> Option<Charset> {
+        let mut rest = &prefix[..prefix.len().min(1024)];
+        while let Some(start) = find_ignore_ascii_case(rest, b"<meta") {
+            let tag = &rest[start + 5..];
+            if !matches!(tag.first(), Some(b' ' | b'\t' | b'\n' | b'\x0c' | b'\r' | b'/')) {
+                rest = tag;
+                continue;
+            }
+            let end = tag.iter().position(|&b| b == b'>').unwrap_or(tag.len());
+            if let Some(charset) = find_attribute(&tag[..end], b"charset") {
+                return Charset::try_from_bytes(charset).ok();
+            }
+            rest = &tag[end..];
+        }
+        None
+    }
+
+    /// Returns the byte order mark written at the start of text in this
+    /// charset.
+    ///
+    /// `UTF-16` and `UTF-32` use the big endian mark. Charsets with an
+    /// explicit byte order like `UTF-16LE` are not prefixed with a BOM (RFC
+    /// 2781) and return `None` like all legacy charsets.
+    pub fn bom(&self) -> Option<&'static [u8]> {
+        match *self {
+            Utf8 => Some(b"\xef\xbb\xbf"),
+            Utf16 => Some(b"\xfe\xff"),
+            Utf32 => Some(b"\x00\x00\xfe\xff"),
+            _ => None,
+        }
+    }
+
+    /// Writes the byte order mark of this charset, if it has one.
+    pub fn write_bom<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        match self.bom() {
+            Some(bom) => w.write_all(bom),
+            None => Ok(()),
+        }
+    }
+
+    /// Removes a leading byte order mark matching this charset.
+    ///
+    /// `UTF-16` and `UTF-32` match the marks of both byte orders. Input
+    /// without a matching BOM is returned unchanged.
+    pub fn strip_bom<'a>(&self, bytes: &'a [u8]) -> &'a [u8] {
+        BOMS.iter()
+            .find(|&&(ref variant, bom)| {
+                bytes.starts_with(bom) &&
+                match *self {
+                    Utf16 => matches!(*variant, Utf16Be | Utf16Le),
+                    Utf32 => matches!(*variant, Utf32Be | Utf32Le),
+                    _ => variant == self,
+                }
+            })
+            .map_or(bytes, |&(_, bom)| &bytes[bom.len()..])
+    }
+
+    /// Detects a charset from the byte order mark at the start of `bytes`.
+    pub fn from_bom(bytes: &[u8]) -> Option<Charset> {
+        BOMS.iter()
+            .find(|&&(_, bom)| bytes.starts_with(bom))
+            .map(|(variant, _)| variant.clone())
+    }
+
+    /// Extracts the charset of an XML document from its byte order mark or
+    /// the `encoding` pseudo-attribute of the `<?xml ...?>` declaration.
+    ///
+    /// A byte order mark takes precedence over the declaration.
+    pub fn parse_xml_declaration(prefix: &[u8]) -> Option<Charset> {
+        if let Some(charset) = Charset::from_bom(prefix) {
+            return Some(charset);
+        }
+        let prolog = prefix.strip_prefix(b"<?xml")?;
+        if !matches!(prolog.first(), Some(b' ') | Some(b'\t') | Some(b'\r') | Some(b'\n')) {
+            return None;
+        }
+        let end = prolog.windows(2).position(|w| w == b"?>").unwrap_or(prolog.len());
+        let encoding = find_attribute(&prolog[..end], b"encoding")?;
+        Charset::try_from_bytes(encoding).ok()
+    }
+
+    /// Reads up to 1024 bytes from `reader` and detects the charset from them.
+    ///
+    /// A byte order mark is checked first, then an XML declaration and a HTML
+    /// `<meta>` tag. Falls back to `UTF-8` if none is found. The bytes read
+    /// are returned so the caller can chain them with the rest of the stream.
+    pub fn sniff_from_reader<R: Read>(reader: &mut R) -> io::Result<(Charset, Vec<u8>)> {
+        let mut prefix = Vec::with_capacity(1024);
+        reader.by_ref().take(1024).read_to_end(&mut prefix)?;
+        let charset = Charset::parse_xml_declaration(&prefix)
+                          .or_else(|| Charset::parse_meta_charset(&prefix))
+                          .unwrap_or(Utf8);
+        Ok((charset, prefix))
+    }
+
+    /// Parses a charset, ignoring surrounding whitespace, quotes, angle
+    /// brackets and semicolons sent by sloppy servers.
+    ///
+    /// Unlike `FromStr` this never fails, unknown labels longer than
+    /// `MAX_LABEL_LEN` are truncated.
+    pub fn from_lenient(s: &str) -> Charset {
+        Charset::from_label(s.trim_matches(|c: char| {
+            c.is_whitespace() || c == ';' || c == '"' || c == '\'' || c == '<' || c == '>'
+        }))
+    }
+
+    /// Extracts the charset of a `Content-Type` header value, rejecting
+    /// malformed headers.
+    ///
+    /// The value must follow the `media-type` grammar of RFC 7231, with
+    /// parameter values as tokens or quoted strings. Returns `Ok(None)` if
+    /// there is no `charset` parameter and `Error::Invalid` with the header
+    /// value on syntax errors, like an unterminated quoted string or an empty
+    /// parameter, and if the `charset` parameter is repeated.
+    pub fn from_content_type_strict(value: &str) -> Result<Option<Charset>> {
+        let invalid = || Error::invalid(value.as_bytes());
+        let (type_, rest) = split_token(value.trim());
+        let (subtype, mut rest) = split_token(rest.strip_prefix('/').ok_or_else(invalid)?);
+        if type_.is_empty() || subtype.is_empty() {
+            return Err(invalid());
+        }
+        let mut charset = None;
+        loop {
+            rest = rest.trim_start_matches(&[' ', '\t'][..]);
+            if rest.is_empty() {
+                return Ok(charset);
+            }
+            rest = rest.strip_prefix(';').ok_or_else(invalid)?.trim_start_matches(&[' ', '\t'][..]);
+            let (name, after) = split_token(rest);
+            let after = after.strip_prefix('=').filter(|_| !name.is_empty()).ok_or_else(invalid)?;
+            let (param, after) = match after.strip_prefix('"') {
+                Some(quoted) => split_quoted_string(quoted).ok_or_else(invalid)?,
+                None => {
+                    let (token, after) = split_token(after);
+                    if token.is_empty() {
+                        return Err(invalid());
+                    }
+                    (token.to_owned(), after)
+                }
+            };
+            if name.eq_ignore_ascii_case("charset") {
+                if param.is_empty() || charset.is_some() {
+                    return Err(invalid());
+                }
+                charset = Some(param.parse().map_err(|_| invalid())?);
+            }
+            rest = after;
+        }
+    }
+
+    /// Determines the charset of a document by applying the usual precedence:
+    ///
+    /// 1. The `declared` charset parameter, if given and not blank, wins.
+    /// 2. Otherwise a byte order mark at the start of `content_prefix`.
+    /// 3. Otherwise the default of the media type: `UTF-8` for JSON and XML
+    ///    types, including `+json` and `+xml` suffixes (RFC 8259, RFC 7303),
+    ///    and `US-ASCII` for `text/*` types (RFC 2046).
+    /// 4. Otherwise `US-ASCII`.
+    ///
+    /// `media_type` may include parameters, they are ignored.
+    pub fn resolve(media_type: &str, declared: Option<&str>, content_prefix: &[u8]) -> Charset {
+        if let Some(declared) = declared.filter(|declared| !declared.trim().is_empty()) {
+            return Charset::from_lenient(declared);
+        }
+        if let Some(charset) = Charset::from_bom(content_prefix) {
+            return charset;
+        }
+        let media_type = media_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+        let (type_, subtype) = media_type.split_once('/').unwrap_or((&media_type, ""));
+        match (type_, subtype) {
+            ("application", "json") | ("application", "xml") | ("text", "xml") => Utf8,
+            (_, subtype) if subtype.ends_with("+json") || subtype.ends_with("+xml") => Utf8,
+            _ => UsAscii,
+        }
+    }
+
+    /// Extracts the charset of a `Content-Type` header value.
+    ///
+    /// Comments in parentheses, like in `text/plain; charset=utf-8 (Unicode)`,
+    /// are ignored as in RFC 822 and RFC 2045 headers. Returns `None` if there
+    /// is no `charset` parameter.
+    pub fn from_content_type(value: &str) -> Option<Charset> {
+        Charset::content_type_charsets(&strip_comments(value)).into_iter().next()
+    }
+
+    /// Extracts the charset of a `Content-Type` header value like
+    /// `from_content_type`, returning `default` if there is none or it is
+    /// empty.
+    pub fn from_content_type_or(value: &str, default: Charset) -> Charset {
+        Charset::from_content_type(value).unwrap_or(default)
+    }
+
+    /// Returns the charsets of all `charset` parameters of a `Content-Type`
+    /// header value in order.
+    ///
+    /// A valid header has at most one, more than one element lets callers
+    /// detect and reject duplicates. Quoted strings are unescaped and may
+    /// contain semicolons. Unlike `from_content_type_strict` malformed
+    /// parameters are skipped, as are empty values.
+    pub fn content_type_charsets(value: &str) -> Vec<Charset> {
+        let whitespace = &[' ', '\t'][..];
+        let mut charsets = Vec::new();
+        let mut rest = match value.find(';') {
+            Some(i) => &value[i..],
+            None => return charsets,
+        };
+        while let Some(param) = rest.strip_prefix(';') {
+            let (name, after) = split_token(param.trim_start_matches(whitespace));
+            let after = after.trim_start_matches(whitespace);
+            let (param, after) = match after.strip_prefix('=') {
+                Some(after) if !name.is_empty() => {
+                    let after = after.trim_start_matches(whitespace);
+                    match after.strip_prefix('"') {
+                        Some(quoted) => match split_quoted_string(quoted) {
+                            Some((param, after)) => (param, after),
+                            None => break,
+                        },
+                        None => {
+                            let (token, after) = split_token(after);
+                            (token.to_owned(), after)
+                        }
+                    }
+                }
+                _ => (String::new(), after),
+            };
+            if !param.is_empty() && name.eq_ignore_ascii_case("charset") {
+                charsets.extend(param.parse().ok());
+            }
+            rest = &after[after.find(';').unwrap_or(after.len())..];
+        }
+        charsets
+    }
+
+    /// Builds a `Content-Type` header value for `media_type` with this
+    /// charset, like `text/html; charset=UTF-8`.
+    ///
+    /// The preferred MIME name is used. Names that are not a valid token are
+    /// sent as a quoted string.
+    pub fn to_content_type(&self, media_type: &str) -> String {
+        let name = self.preferred_mime_name();
+        if is_token(name) {
+            return format!("{}; charset={}", media_type, name);
+        }
+        let mut value = format!("{}; charset=\"", media_type);
+        for c in name.chars() {
+            if c == '"' || c == '\\' {
+                value.push('\\');
+            }
+            value.push(c);
+        }
+        value.push('"');
+        value
+    }
+
+    /// Builds a `Content-Type` header value for `media_type` with this charset.
+    ///
+    /// The value is the same as `to_content_type` returns.
+    #[cfg(feature = "http")]
+    pub fn to_content_type_header(&self,
+                                  media_type: &str)
+                                  -> ::std::result::Result<HeaderValue, InvalidHeaderValue> {
+        HeaderValue::from_str(&self.to_content_type(media_type))
+    }
+
+    /// Converts a charset parameter value of the `mime` crate.
+    #[cfg(feature = "mime")]
+    pub fn from_mime(name: mime::Name) -> Charset {
+        Charset::from_label(name.as_str())
+    }
+
+    /// Returns the name in lower case, as the `mime` crate normalizes charset
+    /// parameters.
+    #[cfg(feature = "mime")]
+    pub fn to_mime_string(&self) -> String {
+        self.name().to_ascii_lowercase()
+    }
+
+    /// Parses a charset, accepting underscores in place of hyphens like
+    /// `UTF_8` or `EUC_JP`.
+    ///
+    /// Labels that are registered with an underscore, like `Shift_JIS`, are
+    /// matched as they are. Unknown labels are returned as `Unregistered`,
+    /// truncated to `MAX_LABEL_LEN` bytes.
+    pub fn parse_relaxed(s: &str) -> Charset {
+        let s = truncate_str(s.trim());
+        Charset::lookup(s)
+            .or_else(|| Charset::lookup(&s.replace('_', "-")))
+            .unwrap_or_else(|| Unregistered(s.to_owned()))
+    }
+
+    /// Parses a charset, returning `fallback` if the input is blank or not a
+    /// registered charset.
+    pub fn parse_with_fallback(s: &str, fallback: Charset) -> Charset {
+        match s.trim().parse() {
+            Ok(Unregistered(_)) | Err(_) => fallback,
+            Ok(charset) => charset,
+        }
+    }
+
+    /// Converts a charset name used by Java, like `ISO8859_1` or `SJIS`.
+    ///
+    /// Besides the Java names, IANA names are accepted with underscores in
+    /// place of hyphens. Returns `None` for unknown names.
+    pub fn from_java_name(name: &str) -> Option<Charset> {
+        JAVA_NAMES.iter()
+                  .find(|&(_, java_name)| java_name.eq_ignore_ascii_case(name))
+                  .map(|(variant, _)| variant.clone())
+                  .or_else(|| Charset::lookup(name))
+                  .or_else(|| Charset::lookup(&name.replace('_', "-")))
+    }
+
+    /// Converts a Python codec name, like `latin_1` or `cp1252`.
+    ///
+    /// Like Python, the name is matched ignoring case with spaces and hyphens
+    /// treated as underscores. IANA names are accepted too, also with
+    /// underscores in place of hyphens. Returns `None` for unknown names.
+    pub fn from_python_name(name: &str) -> Option<Charset> {
+        let label = name.trim();
+        let name = label.to_ascii_lowercase().replace(&['-', ' '][..], "_");
+        PYTHON_NAMES.iter()
+                    .find(|&&(_, python_name)| python_name == name)
+                    .map(|(variant, _)| variant.clone())
+                    .or_else(|| Charset::lookup(label))
+                    .or_else(|| Charset::lookup(&name.replace('_', "-")))
+    }
+
+    /// Converts an Emacs coding system name, like `latin-1` or
+    /// `japanese-shift-jis`.
+    ///
+    /// The end-of-line suffixes `-unix`, `-dos` and `-mac` are ignored. IANA
+    /// names are accepted too. Returns `None` for unknown names.
+    pub fn from_emacs_coding_system(name: &str) -> Option<Charset> {
+        let name = name.trim().to_ascii_lowercase();
+        let base = ["-unix", "-dos", "-mac"].iter()
+                                             .find_map(|suffix| name.strip_suffix(suffix))
+                                             .unwrap_or(&name);
+        EMACS_NAMES.iter()
+                   .find(|&&(_, emacs_name)| emacs_name == base)
+                   .map(|(variant, _)| variant.clone())
+                   .or_else(|| Charset::lookup(base))
+    }
+
+    /// Extracts the charset declared by a coding cookie in the first two lines
+    /// of a script or source file.
+    ///
+    /// Like in PEP 263 `coding: utf-8` or `coding=utf-8` is recognized
+    /// anywhere in a comment line starting with `#`. In other lines the
+    /// cookie must be inside an Emacs `-*- coding: latin-1 -*-` block. The
+    /// name is resolved with `from_python_name`, `from_emacs_coding_system`
+    /// and finally `FromStr`.
+    pub fn sniff_coding_cookie(prefix: &[u8]) -> Option<Charset> {
+        for line in prefix.split(|&b| b == b'\n').take(2) {
+            let start = line.iter().position(|&b| !b" \t\x0c".contains(&b)).unwrap_or(line.len());
+            let mut rest = if line[start..].starts_with(b"#") {
+                &line[start..]
+            } else {
+                match find_ignore_ascii_case(line, b"-*-") {
+                    Some(open) => {
+                        let block = &line[open + 3..];
+                        match find_ignore_ascii_case(block, b"-*-") {
+                            Some(close) => &block[..close],
+                            None => continue,
+                        }
+                    }
+                    None => continue,
+                }
+            };
+            while let Some(start) = find_ignore_ascii_case(rest, b"coding") {
+                rest = &rest[start + 6..];
+                if rest.first() != Some(&b':') && rest.first() != Some(&b'=') {
+                    continue;
+                }
+                let value = trim_ascii_start(&rest[1..]);
+                let end = value.iter()
+                               .position(|&b| {
+                                   !(b.is_ascii_alphanumeric() || b"-_.".contains(&b))
+                               })
+                               .unwrap_or(value.len());
+                let name = match str::from_utf8(&value[..end]) {
+                    Ok(name) if !name.is_empty() => name,
+                    _ => continue,
+                };
+                return Charset::from_python_name(name)
+                    .or_else(|| Charset::from_emacs_coding_system(name))
+                    .or_else(|| name.parse().ok());
+            }
+        }
+        None
+    }
+
+    /// Parses an `Accept-Charset` header into charsets and their quality
+    /// values.
+    ///
+    /// The wildcard `*` is returned as `Unregistered("*")`. Entries with an
+    /// invalid quality value are skipped.
+    pub fn parse_accept_charset(header: &str) -> Vec<(Charset, f32)> {
+        header.split(',')
+              .filter_map(|item| {
+                  let mut parts = item.split(';');
+                  let charset = parts.next()?.trim();
+                  if charset.is_empty() {
+                      return None;
+                  }
+                  let mut q = 1.0;
+                  for param in parts.map(str::trim) {
+                      if starts_with_ignore_ascii_case(param, "q=") {
+                          q = param[2..].trim()
+                                        .parse()
+                                        .ok()
+                                        .filter(|q| (0.0..=1.0).contains(q))?;
+                      }
+                  }
+                  charset.parse().ok().map(|charset| (charset, q))
+              })
+              .collect()
+    }
+
+    /// Parses multiple `Accept-Charset` header lines as one comma-separated
+    /// list, like HTTP requires for repeated headers.
+    pub fn parse_accept_charset_multi(headers: &[&str]) -> Vec<(Charset, f32)> {
+        Charset::parse_accept_charset(&headers.join(","))
+    }
+
+    /// Parses an `Accept-Charset` header like `parse_accept_charset` and sorts
+    /// the entries by descending quality.
+    ///
+    /// Entries of equal quality are ordered by `preferred_over` and then by
+    /// their position in the header. Entries with quality 0 are dropped.
+    pub fn parse_accept_charset_sorted(header: &str) -> Vec<(Charset, f32)> {
+        let mut entries = Charset::parse_accept_charset(header);
+        entries.retain(|&(_, q)| q > 0.0);
+        entries.sort_by(|(a, a_q), (b, b_q)| {
+            b_q.partial_cmp(a_q).unwrap_or(Ordering::Equal).then_with(|| {
+                if a.preferred_over(b) {
+                    Ordering::Less
+                } else if b.preferred_over(a) {
+                    Ordering::Greater
+                } else {
+                    Ordering::Equal
+                }
+            })
+        });
+        entries
+    }
+
+    /// Formats this charset as an `Accept-Charset` entry with quality `q`.
+    ///
+    /// The quality is clamped to `[0, 1]`, rounded to three decimals and
+    /// omitted if it is 1. NaN is treated as 0.
+    pub fn format_with_quality(&self, q: f32) -> String {
+        let q = if q.is_nan() { 0.0 } else { q.clamp(0.0, 1.0) };
+        let q = format!("{:.3}", q);
+        let q = q.trim_end_matches('0').trim_end_matches('.');
+        if q == "1" {
+            self.name().to_owned()
+        } else {
+            format!("{};q={}", self.name(), q)
+        }
+    }
+
+    /// Selects the best charset from `supported` for the weighted `accept`
+    /// list, as returned by `parse_accept_charset`.
+    ///
+    /// The charset with the highest quality wins, ties are broken by
+    /// `preferred_over` and then by the order of `accept`. A wildcard `*`
+    /// matches all charsets not listed explicitly. Charsets with quality 0 are
+    /// never selected. If `accept` is empty the first supported charset is
+    /// returned.
+    pub fn negotiate(accept: &[(Charset, f32)], supported: &[Charset]) -> Option<Charset> {
+        if accept.is_empty() {
+            return supported.first().cloned();
+        }
+        let mut best: Option<(&Charset, f32, usize)> = None;
+        for charset in supported {
+            let rank = match accept.iter()
+                                   .position(|(c, _)| c == charset)
+                                   .or_else(|| accept.iter().position(|(c, _)| c.is_wildcard())) {
+                Some(rank) => rank,
+                None => continue,
+            };
+            let q = accept[rank].1;
+            let better = match best {
+                Some((best_charset, best_q, best_rank)) => {
+                    q > best_q ||
+                    (q == best_q &&
+                     (charset.preferred_over(best_charset) ||
+                      (!best_charset.preferred_over(charset) && rank < best_rank)))
+                }
+                None => true,
+            };
+            if q > 0.0 && better {
+                best = Some((charset, q, rank));
+            }
+        }
+        best.map(|(charset, _, _)| charset.clone())
+    }
+
+    /// Combines a client `Accept-Charset` list with a server preference list.
+    ///
+    /// For each charset of `server` that the client accepts, explicitly or by
+    /// a wildcard `*`, the weights are multiplied. Entries with weight 0 are
+    /// dropped and the result is sorted by descending weight, keeping the
+    /// server order for equal weights.
+    pub fn merge_preferences(client: &[(Charset, f32)],
+                             server: &[(Charset, f32)])
+                             -> Vec<(Charset, f32)> {
+        let mut merged: Vec<(Charset, f32)> =
+            server.iter()
+                  .filter_map(|(charset, server_q)| {
+                      client.iter()
+                            .find(|(c, _)| c == charset)
+                            .or_else(|| client.iter().find(|(c, _)| c.is_wildcard()))
+                            .map(|&(_, client_q)| (charset.clone(), client_q * server_q))
+                  })
+                  .filter(|&(_, q)| q > 0.0)
+                  .collect();
+        merged.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(Ordering::Equal));
+        merged
+    }
+
+    /// Returns true if a server should prefer this charset over `other` when
+    /// a client accepts both equally.
+    ///
+    /// The ranking is `UTF-8`, then `UTF-16`, then `UTF-32`, then all other
+    /// charsets, which are not preferred over each other.
+    pub fn preferred_over(&self, other: &Charset) -> bool {
+        fn rank(charset: &Charset) -> u8 {
+            match *charset {
+                Utf8 => 3,
+                Utf16 | Utf16Be | Utf16Le => 2,
+                Utf32 | Utf32Be | Utf32Le => 1,
+                _ => 0,
+            }
+        }
+        rank(self) > rank(other)
+    }
+
+    fn is_wildcard(&self) -> bool {
+        match *self {
+            Unregistered(ref s) => s == "*",
+            _ => false,
+        }
+    }
+
+    /// Normalizes a label to the canonical name without keeping a `Charset`,
+    /// for example to build cache keys.
+    ///
+    /// The label is trimmed and names and aliases of registered charsets are
+    /// replaced by the canonical name. Other labels are returned trimmed but
+    /// otherwise unchanged.
+    pub fn normalize_label(label: &str) -> String {
+        let label = label.trim();
+        match Charset::lookup(label) {
+            Some(charset) => charset.name().to_owned(),
+            None => label.to_owned(),
+        }
+    }
+
+    /// Parses a charset token from the start of `s` and returns it with the
+    /// rest of the string.
+    ///
+    /// Leading whitespace is skipped and the token ends at the first
+    /// character not allowed in an RFC 7230 token, like `;`, `,` or
+    /// whitespace. Returns `None` if there is no token. Unknown tokens longer
+    /// than `MAX_LABEL_LEN` are truncated.
+    pub fn parse_prefix(s: &str) -> Option<(Charset, &str)> {
+        let (token, rest) = split_token(s.trim_start());
+        if token.is_empty() {
+            return None;
+        }
+        Some((Charset::from_label(token), rest))
+    }
+
+    /// Parses a comma-separated list of charsets.
+    ///
+    /// Each token is trimmed, empty tokens and tokens rejected by `FromStr`
+    /// are skipped. Quality values are not supported.
+    pub fn parse_all(list: &str) -> Vec<Charset> {
+        list.split(',')
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+            .filter_map(|token| token.parse().ok())
+            .collect()
+    }
+
+    /// Extracts the charset of an RFC 5987 ext-value, as used by
+    /// `filename*=UTF-8''name` parameters.
+    ///
+    /// Returns `None` if the value has no charset part.
+    pub fn from_ext_value(ext_value: &str) -> Option<Charset> {
+        let end = ext_value.find('\'')?;
+        let charset = ext_value[..end].trim();
+        if charset.is_empty() {
+            return None;
+        }
+        charset.parse().ok()
+    }
+
+    /// Parses a percent-encoded charset label, like `UTF%2D8`.
+    ///
+    /// Returns `None` for malformed escapes, empty labels and labels that are
+    /// not ASCII after decoding.
+    pub fn from_percent_encoded(s: &str) -> Option<Charset> {
+        let mut decoded = Vec::with_capacity(s.len());
+        let mut bytes = s.bytes();
+        while let Some(b) = bytes.next() {
+            if b == b'%' {
+                let hex = [bytes.next()?, bytes.next()?];
+                if !hex.iter().all(u8::is_ascii_hexdigit) {
+                    return None;
+                }
+                decoded.push(u8::from_str_radix(str::from_utf8(&hex).ok()?, 16).ok()?);
+            } else {
+                decoded.push(b);
+            }
+        }
+        if decoded.is_empty() {
+            return None;
+        }
+        Charset::try_from_bytes(&decoded).ok()
+    }
+
+    /// Parses a comma-separated list of charsets into the registered charsets
+    /// and the tokens that are not registered.
+    ///
+    /// Tokens are trimmed and empty tokens are skipped.
+    pub fn parse_list_partitioned(list: &str) -> (Vec<Charset>, Vec<String>) {
+        let mut registered = Vec::new();
+        let mut rejected = Vec::new();
+        for token in list.split(',').map(str::trim).filter(|token| !token.is_empty()) {
+            match Charset::lookup(token) {
+                Some(charset) => registered.push(charset),
+                None => rejected.push(token.to_owned()),
+            }
+        }
+        (registered, rejected)
+    }
+
+    /// Parses a charset from raw bytes, like a header value.
+    ///
+    /// Returns `Error::Invalid` if the bytes are not ASCII or longer than
+    /// `MAX_LABEL_LEN`. This function never panics and allocates at most
+    /// `MAX_LABEL_LEN` bytes, also for the label kept in the error, so it is
+    /// safe to use on untrusted input.
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Charset> {
+        Charset::parse_bytes_with_limit(bytes, MAX_LABEL_LEN)
+    }
+
+    /// Parses a charset from raw bytes like `try_from_bytes`, but with the
+    /// maximum length `max_len` instead of `MAX_LABEL_LEN`.
+    ///
+    /// Returns `Error::Invalid` if the bytes are not ASCII or longer than
+    /// `max_len`.
+    pub fn parse_bytes_with_limit(bytes: &[u8], max_len: usize) -> Result<Charset> {
+        if bytes.len() > max_len {
+            return Err(Error::invalid(bytes));
+        }
+        match str::from_utf8(bytes) {
+            Ok(s) if s.is_ascii() => Ok(Charset::from_label(s)),
+            _ => Err(Error::invalid(bytes)),
+        }
+    }
+
+    /// Suggests the registered charset with a name or alias closest to
+    /// `label`, for "did you mean" hints in error messages.
+    ///
+    /// Labels are compared ignoring case by edit distance, which may be at
+    /// most 2 and less than half the length of `label`. Returns `None` if no
+    /// label is close enough.
+    pub fn suggest(label: &str) -> Option<Charset> {
+        let label = label.trim().to_ascii_lowercase();
+        REGISTRY.iter()
+                .filter_map(|info| {
+                    iter::once(info.name)
+                        .chain(info.aliases.iter().cloned())
+                        .map(|candidate| edit_distance(&label, &candidate.to_ascii_lowercase()))
+                        .min()
+                        .map(|distance| (distance, &info.charset))
+                })
+                .filter(|&(distance, _)| distance <= 2 && distance * 2 < label.len())
+                .min_by_key(|&(distance, _)| distance)
+                .map(|(_, charset)| charset.clone())
+    }
+
+    /// Parses a registered charset, reporting why a label was rejected.
+    ///
+    /// The label is trimmed. Returns `Error::Empty` for blank labels,
+    /// `Error::NonAscii` for labels with non-ASCII characters,
+    /// `Error::Invalid` for labels that are too long or not an RFC 7230 token
+    /// and `Error::Unknown` for labels that are not registered.
+    pub fn from_label_detailed(label: &str) -> Result<Charset> {
+        let label = label.trim();
+        if label.is_empty() {
+            return Err(Error::Empty);
+        }
+        if !label.is_ascii() {
+            return Err(Error::NonAscii);
+        }
+        if label.len() > MAX_LABEL_LEN || !is_token(label) {
+            return Err(Error::invalid(label.as_bytes()));
+        }
+        Charset::lookup(label).ok_or_else(|| Error::Unknown(truncate_label(label.as_bytes())))
+    }
+
+    /// Parses a registered charset, rejecting everything else.
+    ///
+    /// Returns `Error::Empty` for blank input and `Error::Invalid` for labels
+    /// that are not an RFC 7230 token or not the name or alias of a
+    /// registered charset. Use `from_label_detailed` to tell these apart.
+    pub fn parse_strict(s: &str) -> Result<Charset> {
+        if s.trim().is_empty() {
+            return Err(Error::Empty);
+        }
+        if s.len() > MAX_LABEL_LEN || !is_token(s) {
+            return Err(Error::invalid(s.as_bytes()));
+        }
+        Charset::lookup(s).ok_or_else(|| Error::invalid(s.as_bytes()))
+    }
+
+    /// Looks up `s`, or stores it truncated to `MAX_LABEL_LEN` bytes.
+    fn from_label(s: &str) -> Charset {
+        Charset::lookup(s).unwrap_or_else(|| Unregistered(truncate_str(s).to_owned()))
+    }
+
+    fn lookup(s: &str) -> Option<Charset> {
+        Charset::intern(s).cloned()
+    }
+
+    /// Returns a reference to the registered charset with the name or alias
+    /// `label`, ignoring case.
+    ///
+    /// All labels of a charset return the same reference into the registry,
+    /// so no `Charset` is allocated or cloned. Returns `None` for unknown
+    /// labels.
+    pub fn intern(label: &str) -> Option<&'static Charset> {
+        REGISTRY.iter()
+                .find(|info| {
+                    info.name.eq_ignore_ascii_case(label) ||
+                    info.aliases.iter().any(|alias| alias.eq_ignore_ascii_case(label))
+                })
+                .map(|info| &info.charset)
+    }
+}
+
+/// Formats the canonical name, or the preferred MIME name with `{:#}`.
+impl Display for Charset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            f.write_str(self.preferred_mime_name())
+        } else {
+            f.write_str(self.name())
+        }
+    }
+}
+
+impl FromStr for Charset {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Charset> {
+        if s.len() > MAX_LABEL_LEN {
+            return Err(Error::invalid(s.as_bytes()));
+        }
+        Ok(Charset::from_label(s))
+    }
+}
+
+impl TryFrom<String> for Charset {
+    type Error = Error;
+    /// Like `FromStr`, but moves the string into `Unregistered` instead of
+    /// copying it.
+    fn try_from(s: String) -> Result<Charset> {
+        if s.len() > MAX_LABEL_LEN {
+            return Err(Error::invalid(s.as_bytes()));
+        }
+        match Charset::lookup(&s) {
+            Some(charset) => Ok(charset),
+            None => Ok(Unregistered(s)),
+        }
+    }
+}
+
+/// Hashes the lower-cased name, so charsets that compare equal ignoring case
+/// also hash equal.
+impl Hash for Charset {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for b in self.name().bytes() {
+            state.write_u8(b.to_ascii_lowercase());
+        }
+        state.write_u8(0xff);
+    }
+}
+
+/// Charsets are ordered by their MIBenum, followed by unregistered charsets in
+/// alphabetical order ignoring case.
+///
+/// The order does not depend on the declaration order of the variants, adding
+/// new charsets never changes the relative order of existing ones.
+impl Ord for Charset {
+    fn cmp(&self, other: &Charset) -> Ordering {
+        match (self, other) {
+            (Unregistered(s), Unregistered(t)) => {
+                s.bytes()
+                 .map(|b| b.to_ascii_lowercase())
+                 .cmp(t.bytes().map(|b| b.to_ascii_lowercase()))
+            }
+            _ => self.cmp_by_mibenum(other),
+        }
+    }
+}
+
+impl PartialOrd for Charset {
+    fn partial_cmp(&self, other: &Charset) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(feature = "http")]
+impl TryFrom<Charset> for HeaderValue {
+    type Error = InvalidHeaderValue;
+    fn try_from(charset: Charset) -> ::std::result::Result<HeaderValue, InvalidHeaderValue> {
+        HeaderValue::from_str(charset.name())
+    }
+}
+
+impl PartialEq for Charset {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (&UsAscii, &UsAscii) |
+            (&Iso88591, &Iso88591) |
+            (&Iso88592, &Iso88592) |
+            (&Iso88593, &Iso88593) |
+            (&Iso88594, &Iso88594) |
+            (&Iso88595, &Iso88595) |
+            (&Iso88596, &Iso88596) |
+            (&Iso88597, &Iso88597) |
+            (&Iso88598, &Iso88598) |
+            (&Iso88599, &Iso88599) |
+            (&Iso885910, &Iso885910) |
+            (&ShiftJis, &ShiftJis) |
+            (&EucJp, &EucJp) |
+            (&Iso2022Kr, &Iso2022Kr) |
+            (&EucKr, &EucKr) |
+            (&Iso2022Jp, &Iso2022Jp) |
+            (&Iso2022Jp2, &Iso2022Jp2) |
+            (&Iso88596E, &Iso88596E) |
+            (&Iso88596I, &Iso88596I) |
+            (&Iso88598E, &Iso88598E) |
+            (&Iso88598I, &Iso88598I) |
+            (&Gb2312, &Gb2312) |
+            (&Big5, &Big5) |
+            (&Koi8R, &Koi8R) |
+            (&Utf8, &Utf8) |
+            (&Windows1252, &Windows1252) |
+            (&Utf16Be, &Utf16Be) |
+            (&Utf16Le, &Utf16Le) |
+            (&Utf16, &Utf16) |
+            (&Koi8U, &Koi8U) |
+            (&Gbk, &Gbk) |
+            (&Iso885915, &Iso885915) |
+            (&Gb18030, &Gb18030) |
+            (&Utf32, &Utf32) |
+            (&Utf32Be, &Utf32Be) |
+            (&Utf32Le, &Utf32Le) => true,
+            (Unregistered(s), Unregistered(t)) => s.eq_ignore_ascii_case(t),
+            _ => false,
+        }
+    }
+}
+
+/// A weighted list of preferred charsets, for example from a server
+/// configuration.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CharsetPreference {
+    entries: Vec<(Charset, f32)>,
+}
+
+impl CharsetPreference {
+    /// Creates an empty preference list.
+    pub fn new() -> CharsetPreference {
+        CharsetPreference::default()
+    }
+
+    /// Appends a charset with quality `q`.
+    pub fn push(&mut self, charset: Charset, q: f32) {
+        self.entries.push((charset, q));
+    }
+
+    /// Selects the best of the `supported` charsets.
+    ///
+    /// See `Charset::negotiate`, charsets with equal quality are ranked by
+    /// insertion order.
+    pub fn best_supported(&self, supported: &[Charset]) -> Option<Charset> {
+        Charset::negotiate(&self.entries, supported)
+    }
+}
+
+impl Extend<(Charset, f32)> for CharsetPreference {
+    fn extend<T: IntoIterator<Item = (Charset, f32)>>(&mut self, iter: T) {
+        self.entries.extend(iter);
+    }
+}
+
+/// A decoder keeping state between chunks of input, created with
+/// `Charset::new_decoder`.
+///
+/// Byte sequences split across chunks are decoded correctly. For `UTF-16` a
+/// BOM at the start of the input selects the byte order.
+#[cfg(feature = "encoding_rs")]
+pub struct Decoder {
+    /// `None` for charsets decoding every byte up to `max` as one code point
+    /// and for `UTF-16` until enough input was seen to check for a BOM.
+    inner: Option<encoding_rs::Decoder>,
+    pending: Vec<u8>,
+    max: char,
+}
+
+#[cfg(feature = "encoding_rs")]
+impl Decoder {
+    /// Decodes the next chunk, replacing malformed sequences with U+FFFD.
+    ///
+    /// Set `last` for the final chunk, so an incomplete trailing sequence is
+    /// replaced instead of kept for the next chunk.
+    pub fn decode_chunk(&mut self, mut input: &[u8], last: bool) -> String {
+        let inner = match self.inner {
+            Some(ref mut inner) => inner,
+            None if self.max < char::MAX => return decode_latin1(input, self.max).into_owned(),
+            None => {
+                self.pending.extend_from_slice(input);
+                if self.pending.len() < 2 && !last {
+                    return String::new();
+                }
+                let pending = ::std::mem::take(&mut self.pending);
+                let (encoding, rest) = Utf16.decoder_input(&pending).unwrap();
+                self.inner = Some(encoding.new_decoder_without_bom_handling());
+                return self.decode_chunk(rest, last);
+            }
+        };
+        let mut output = String::new();
+        loop {
+            let needed = inner.max_utf8_buffer_length(input.len()).unwrap_or(input.len());
+            output.reserve(needed.max(4));
+            let (result, read, _) = inner.decode_to_string(input, &mut output, last);
+            input = &input[read..];
+            if result == CoderResult::InputEmpty {
+                return output;
+            }
+        }
+    }
+}
+
+/// An encoder keeping state between chunks of input, created with
+/// `Charset::new_encoder`.
+///
+/// Stateful charsets like `ISO-2022-JP` emit escape sequences only when
+/// needed and return to the initial state after the last chunk.
+#[cfg(feature = "encoding_rs")]
+pub struct Encoder {
+    /// `None` for charsets encoding every character up to `max` as one byte.
+    inner: Option<encoding_rs::Encoder>,
+    max: char,
+}
+
+#[cfg(feature = "encoding_rs")]
+impl Encoder {
+    /// Encodes the next chunk and returns the bytes and whether unmappable
+    /// characters were replaced with HTML numeric character references.
+    ///
+    /// Set `last` for the final chunk to reset the encoder state.
+    pub fn encode_chunk(&mut self, mut input: &str, last: bool) -> (Vec<u8>, bool) {
+        let mut output = Vec::new();
+        let mut had_replacements = false;
+        let inner = match self.inner {
+            Some(ref mut inner) => inner,
+            None => {
+                for c in input.chars() {
+                    if c <= self.max {
+                        output.push(c as u8);
+                    } else {
+                        output.extend(format!("&#{};", c as u32).into_bytes());
+                        had_replacements = true;
+                    }
+                }
+                return (output, had_replacements);
+            }
+        };
+        loop {
+            let needed = inner.max_buffer_length_from_utf8_if_no_unmappables(input.len())
+                              .unwrap_or(input.len());
+            output.reserve(needed.max(16));
+            let (result, read, replaced) = inner.encode_from_utf8_to_vec(input, &mut output, last);
+            had_replacements |= replaced;
+            input = &input[read..];
+            if result == CoderResult::InputEmpty {
+                return (output, had_replacements);
+            }
+        }
+    }
+}
+
+/// Decodes every byte up to `max` as the code point of the same value and
+/// replaces the others with U+FFFD.
+#[cfg(feature = "encoding_rs")]
+fn decode_latin1(bytes: &[u8], max: char) -> Cow<'_, str> {
+    match str::from_utf8(bytes) {
+        Ok(s) if s.is_ascii() => Cow::Borrowed(s),
+        _ => {
+            Cow::Owned(bytes.iter()
+                            .map(|&b| b as char)
+                            .map(|c| if c <= max { c } else { '\u{fffd}' })
+                            .collect())
+        }
+    }
+}
+
+/// Returns the position of the first occurrence of `needle` in `haystack`.
+fn find_ignore_ascii_case(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window.eq_ignore_ascii_case(needle))
+}
+
+/// Finds `name=value` in `bytes` and returns the unquoted value.
+fn find_attribute<'a>(mut bytes: &'a [u8], name: &[u8]) -> Option<&'a [u8]> {
+    while let Some(start) = find_ignore_ascii_case(bytes, name) {
+        bytes = trim_ascii_start(&bytes[start + name.len()..]);
+        if bytes.first() != Some(&b'=') {
+            continue;
+        }
+        bytes = trim_ascii_start(&bytes[1..]);
+        let value = match bytes.first() {
+            Some(&quote) if quote == b'"' || quote == b'\'' => {
+                let value = &bytes[1..];
+                &value[..value.iter().position(|&b| b == quote).unwrap_or(value.len())]
+            }
+            _ => {
+                let end = bytes.iter()
+                               .position(|&b| {
+                                   b.is_ascii_whitespace() || b"\"';/>".contains(&b)
+                               })
+                               .unwrap_or(bytes.len());
+                &bytes[..end]
+            }
+        };
+        if !value.is_empty() {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Removes RFC 822 comments, which may be nested, outside of quoted strings.
+fn strip_comments(value: &str) -> String {
+    let mut stripped = String::with_capacity(value.len());
+    let mut depth = 0usize;
+    let mut quoted = false;
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if quoted || depth > 0 => {
+                let escaped = chars.next();
+                if depth == 0 {
+                    stripped.push(c);
+                    stripped.extend(escaped);
+                }
+                continue;
+            }
+            '"' if depth == 0 => quoted = !quoted,
+            '(' if !quoted => depth += 1,
+            ')' if !quoted && depth > 0 => {
+                depth -= 1;
+                continue;
+            }
+            _ => {}
+        }
+        if depth == 0 {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+/// Returns the Levenshtein distance between two ASCII strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let mut row: Vec<usize> = (0..b.len() + 1).collect();
+    for (i, a) in a.bytes().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b) in b.bytes().enumerate() {
+            let substitution = diagonal + (a != b) as usize;
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+fn is_tchar(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+/// Returns true if `s` is a token as defined in RFC 7230.
+fn is_token(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(is_tchar)
+}
+
+/// Splits `s` after the longest token prefix, which may be empty.
+fn split_token(s: &str) -> (&str, &str) {
+    s.split_at(s.bytes().position(|b| !is_tchar(b)).unwrap_or(s.len()))
+}
+
+/// Parses a quoted string after the opening quote, returning the unescaped
+/// content and the rest after the closing quote.
+fn split_quoted_string(s: &str) -> Option<(String, &str)> {
+    let mut content = String::new();
+    let mut chars = s.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((content, &s[i + 1..])),
+            '\\' => content.push(chars.next()?.1),
+            '\t' => content.push(c),
+            c if c.is_control() => return None,
+            c => content.push(c),
+        }
+    }
+    None
+}
+
+fn starts_with_ignore_ascii_case(s: &str, prefix: &str) -> bool {
+    s.len() >= prefix.len() && s.as_bytes()[..prefix.len()].eq_ignore_ascii_case(prefix.as_bytes())
+}
+
+fn trim_ascii_start(bytes: &[u8]) -> &[u8] {
+    let start = bytes.iter().position(|b| !b.is_ascii_whitespace()).unwrap_or(bytes.len());
+    &bytes[start..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{BTreeSet, HashSet};
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(UsAscii, "us-ascii".parse().unwrap());
+        assert_eq!(UsAscii, "US-Ascii".parse().unwrap());
+        assert_eq!(UsAscii, "US-ASCII".parse().unwrap());
+        assert_eq!(ShiftJis, "Shift-JIS".parse().unwrap());
+        assert_eq!(Unregistered("ABCD".to_owned()), "abcd".parse().unwrap());
+        let long = "a".repeat(MAX_LABEL_LEN + 1);
+        assert_eq!(Err(Error::Invalid("a".repeat(MAX_LABEL_LEN))), long.parse::<Charset>());
+        assert!(long[1..].parse::<Charset>().is_ok());
+    }
+
+    #[test]
+    fn test_parse_alias() {
+        assert_eq!(UsAscii, "csUSASCII".parse().unwrap());
+        assert_eq!(ShiftJis, "csShiftJIS".parse().unwrap());
+        assert_eq!(Iso88591, "csisolatin1".parse().unwrap());
+        assert_eq!("Shift-JIS", ShiftJis.to_string());
+        assert_eq!(Iso88591, "iso-ir-100".parse().unwrap());
+        assert_eq!(UsAscii, "ISO-IR-6".parse().unwrap());
+    }
+
+    #[test]
+    fn test_common_web_charsets() {
+        let charsets = Charset::common_web_charsets();
+        assert_eq!(Some(&Utf8), charsets.first());
+        assert!(charsets.contains(&Gb18030));
+        assert!(!charsets.contains(&Iso2022Kr));
+    }
+
+    #[test]
+    fn test_code() {
+        for charset in Charset::all() {
+            assert_eq!(Some(charset.clone()), charset.code().and_then(|code| code.charset()));
+        }
+        let codes: HashSet<CharsetCode> = Charset::all().filter_map(|c| c.code()).collect();
+        assert_eq!(Charset::registry().len(), codes.len());
+        assert_eq!(None, Unregistered("x-foo".to_owned()).code());
+    }
+
+    #[test]
+    fn test_mibenum_be_bytes() {
+        assert_eq!(Some([0, 106]), Utf8.mibenum_be_bytes());
+        assert_eq!(Some(Utf8), Charset::from_mibenum_be_bytes([0, 106]));
+        assert_eq!(Some(Windows1252), Charset::from_mibenum_be_bytes([0x08, 0xcc]));
+        assert_eq!(None, Charset::from_mibenum_be_bytes([0xff, 0xff]));
+        assert_eq!(None, Unregistered("x-foo".to_owned()).mibenum_be_bytes());
+    }
+
+    #[test]
+    fn test_cmp_by_mibenum() {
+        let unregistered = Unregistered("x-foo".to_owned());
+        assert_eq!(Ordering::Less, UsAscii.cmp_by_mibenum(&Iso88591));
+        assert_eq!(Ordering::Less, Iso88591.cmp_by_mibenum(&unregistered));
+        assert_eq!(Ordering::Greater, unregistered.cmp_by_mibenum(&UsAscii));
+        let mut charsets = vec![unregistered.clone(), Iso88591, UsAscii];
+        charsets.sort_by(Charset::cmp_by_mibenum);
+        assert_eq!(vec![UsAscii, Iso88591, unregistered], charsets);
+    }
+
+    #[test]
+    fn test_whatwg_name() {
+        assert_eq!("Shift-JIS", ShiftJis.to_string());
+        assert_eq!(Some("shift_jis"), ShiftJis.whatwg_name());
+        assert_eq!("WINDOWS-1252", Windows1252.to_string());
+        assert_eq!(Some("windows-1252"), Windows1252.whatwg_name());
+        assert_eq!(Some("windows-1252"), Iso88591.whatwg_name());
+        assert_eq!(None, Iso2022Kr.whatwg_name());
+    }
+
+    #[test]
+    fn test_from_codepage() {
+        assert_eq!(Some(Utf8), Charset::from_codepage(65001));
+        assert_eq!(Some(Windows1252), Charset::from_codepage(1252));
+        assert_eq!(Some(ShiftJis), Charset::from_codepage(932));
+        assert_eq!(None, Charset::from_codepage(1));
+    }
+
+    #[test]
+    fn test_codepage() {
+        assert_eq!(Some(65001), Utf8.codepage());
+        assert_eq!(Some(932), ShiftJis.codepage());
+        assert_eq!(Some(20932), EucJp.codepage());
+        assert_eq!(None, Iso88596E.codepage());
+        for charset in Charset::all().filter(|c| c.codepage().is_some()) {
+            assert_eq!(Some(&charset),
+                       Charset::from_codepage(charset.codepage().unwrap()).as_ref());
+        }
+    }
+
+    #[test]
+    fn test_labels() {
+        let labels: Vec<&str> = Iso88591.labels().collect();
+        assert_eq!("ISO-8859-1", labels[0]);
+        assert!(labels.contains(&"latin1"));
+        assert!(!labels.contains(&"latin2"));
+        assert_eq!(vec!["x-foo"], Unregistered("x-foo".to_owned()).labels().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_matches_label_bytes() {
+        assert!(Iso88591.matches_label_bytes(b"LATIN1"));
+        assert!(Iso88591.matches_label_bytes(b"iso-8859-1"));
+        assert!(!Iso88591.matches_label_bytes(b"latin2"));
+        assert!(!Iso88591.matches_label_bytes(b"latin1\xff"));
+        assert!(Unregistered("x-foo".to_owned()).matches_label_bytes(b"X-FOO"));
+    }
+
+    #[test]
+    fn test_has_alias() {
+        assert!(Iso88591.alias_count() > 0);
+        assert!(Iso88591.has_alias("LATIN1"));
+        assert!(!Iso88591.has_alias("ISO-8859-1"));
+        assert!(!Iso88591.has_alias("latin2"));
+        let unregistered = Unregistered("x-foo".to_owned());
+        assert_eq!(0, unregistered.alias_count());
+        assert!(!unregistered.has_alias("x-foo"));
+    }
+
+    #[test]
+    fn test_replacement_char() {
+        assert_eq!(Some(b'?'), Iso88591.replacement_char());
+        assert_eq!(Some(b'?'), Koi8R.replacement_char());
+        assert_eq!(None, Utf8.replacement_char());
+        assert_eq!(None, Iso2022Jp.replacement_char());
+    }
+
+    #[test]
+    fn test_ascii_passthrough() {
+        assert!(UsAscii.ascii_passthrough());
+        assert!(Utf8.ascii_passthrough());
+        assert!(Iso88595.ascii_passthrough());
+        assert!(ShiftJis.ascii_passthrough());
+        assert!(!Iso2022Jp.ascii_passthrough());
+        assert!(!Unregistered("x-ebcdic".to_owned()).ascii_passthrough());
+    }
+
+    #[test]
+    fn test_variants_matching() {
+        assert_eq!(vec![Iso88591, Iso88592, Iso88593, Iso88594, Iso88595, Iso88596, Iso88597,
+                        Iso88598, Iso88599, Iso885910, Iso88596E, Iso88596I, Iso88598E,
+                        Iso88598I, Iso885915],
+                   Charset::variants_matching("iso-8859-"));
+        assert_eq!(vec![Iso88591], Charset::variants_matching("LATIN1"));
+        assert!(Charset::variants_matching("x-").is_empty());
+    }
+
+    #[test]
+    fn test_is_deprecated() {
+        assert!(Iso2022Jp.is_deprecated());
+        assert!(Unregistered("utf-7".to_owned()).is_deprecated());
+        assert!(!Utf8.is_deprecated());
+        assert!(!Unregistered("x-foo".to_owned()).is_deprecated());
+    }
+
+    #[test]
+    fn test_ascii_superset_of() {
+        assert!(Utf8.ascii_superset_of(&UsAscii));
+        assert!(Iso88591.ascii_superset_of(&EucJp));
+        assert!(!Utf16.ascii_superset_of(&UsAscii));
+        assert!(!UsAscii.ascii_superset_of(&Utf16Le));
+    }
+
+    #[test]
+    fn test_canonical_eq() {
+        let latin1 = Unregistered("latin1".to_owned());
+        assert_eq!(Iso88591, latin1.canonicalize());
+        assert!(Iso88591 != latin1);
+        assert!(Iso88591.canonical_eq(&latin1));
+        assert!(latin1.canonical_eq(&Unregistered("ISO-8859-1".to_owned())));
+        assert!(!latin1.canonical_eq(&Iso88592));
+        assert!(Unregistered("x-a".to_owned()).canonical_eq(&Unregistered("X-A".to_owned())));
+    }
+
+    #[test]
+    fn test_requires_explicit_declaration() {
+        assert!(!Utf8.requires_explicit_declaration());
+        assert!(!UsAscii.requires_explicit_declaration());
+        assert!(Iso88591.requires_explicit_declaration());
+    }
+
+    #[test]
+    fn test_covers_unicode() {
+        assert!(Gb18030.covers_unicode());
+        assert!(Utf32Le.covers_unicode());
+        assert!(!Iso88591.covers_unicode());
+        assert!(!Gbk.covers_unicode());
+    }
+
+    #[test]
+    fn test_web_decoding_charset() {
+        assert_eq!(Windows1252, Iso88591.web_decoding_charset());
+        assert_eq!(Windows1252, UsAscii.web_decoding_charset());
+        assert_eq!(ShiftJis, ShiftJis.web_decoding_charset());
+        assert_eq!("ISO-8859-1", Iso88591.to_string());
+    }
+
+    #[test]
+    fn test_parse_koi8_alias() {
+        assert_eq!(Koi8R, "csKOI8R".parse().unwrap());
+        assert_eq!(Koi8U, "cskoi8u".parse().unwrap());
+        assert_eq!(Koi8U, "KOI8-U".parse().unwrap());
+    }
+
+    #[test]
+    fn test_try_from_string() {
+        assert_eq!(Ok(Utf8), Charset::try_from("UTF-8".to_owned()));
+        let s = "x-custom".to_owned();
+        let ptr = s.as_ptr();
+        match Charset::try_from(s) {
+            Ok(Unregistered(ref t)) => assert_eq!(ptr, t.as_ptr()),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        for charset in Charset::all() {
+            assert_eq!(Ok(&charset),
+                       charset.name().parse::<Charset>().as_ref(),
+                       "{:?} does not round-trip",
+                       charset);
+        }
+        assert_eq!(Big5, "big5".parse().unwrap());
+        assert_eq!("Big5", Big5.to_string());
+    }
+
+    #[test]
+    fn test_intern() {
+        let a = Charset::intern("utf-8").unwrap();
+        let b = Charset::intern("UTF-8").unwrap();
+        assert!(::std::ptr::eq(a, b));
+        assert_eq!(&Utf8, a);
+        assert!(::std::ptr::eq(Charset::intern("latin1").unwrap(),
+                               Charset::intern("ISO-8859-1").unwrap()));
+        assert_eq!(None, Charset::intern("x-foo"));
+    }
+
+    #[test]
+    fn test_all_with_mibenum() {
+        assert_eq!(Charset::registered_count(), Charset::all_with_mibenum().count());
+        assert_eq!(Some((UsAscii, 3)), Charset::all_with_mibenum().next());
+        for (charset, mibenum) in Charset::all_with_mibenum() {
+            assert_eq!(Some(mibenum), charset.mibenum());
+        }
+    }
+
+    #[test]
+    fn test_from_registry_index() {
+        assert_eq!(Some(UsAscii), Charset::from_registry_index(0));
+        let all: Vec<Charset> = (0..Charset::registered_count())
+                                    .map(|i| Charset::from_registry_index(i).unwrap())
+                                    .collect();
+        assert_eq!(Charset::all().collect::<Vec<_>>(), all);
+        assert_eq!(None, Charset::from_registry_index(Charset::registered_count()));
+    }
+
+    #[test]
+    fn test_registry() {
+        assert_eq!(Charset::all().count(), Charset::registry().len());
+        for info in Charset::registry() {
+            assert_eq!(info.charset, info.name.parse().unwrap());
+            assert_eq!(info.name, info.charset.to_string());
+            assert_eq!(Some(info.mibenum), info.charset.mibenum());
+            for alias in info.aliases {
+                assert_eq!(info.charset, alias.parse().unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!("US-ASCII", UsAscii.to_string());
+        assert_eq!("ABCD", Unregistered("ABCD".to_owned()).to_string());
+    }
+
+    #[test]
+    fn test_as_bytes_name() {
+        assert_eq!(b"US-ASCII", UsAscii.as_bytes_name());
+        assert_eq!(b"x-foo", Unregistered("x-foo".to_owned()).as_bytes_name());
+    }
+
+    #[test]
+    fn test_from_ext_value() {
+        assert_eq!(Some(Utf8), Charset::from_ext_value("UTF-8''%e2%82%ac%20rates"));
+        assert_eq!(Some(Iso88591), Charset::from_ext_value("ISO-8859-1'en'%A3%20rates"));
+        assert_eq!(None, Charset::from_ext_value("''name"));
+        assert_eq!(None, Charset::from_ext_value("name.txt"));
+    }
+
+    #[test]
+    fn test_byte_order_independent() {
+        assert!(!Utf16.byte_order_independent());
+        assert!(!Utf32.byte_order_independent());
+        assert!(Utf16Le.byte_order_independent());
+        assert!(Iso88591.byte_order_independent());
+    }
+
+    #[test]
+    fn test_is_utf8() {
+        assert!(Utf8.is_utf8());
+        assert!(!UsAscii.is_utf8());
+        assert!(!Unregistered("utf8".to_owned()).is_utf8());
+    }
+
+    #[test]
+    fn test_is_private_use() {
+        assert!(Unregistered("x-foo".to_owned()).is_private_use());
+        assert!(Unregistered("X-Mac-Roman".to_owned()).is_private_use());
+        assert!(!Unregistered("weird".to_owned()).is_private_use());
+        assert!(!Utf8.is_private_use());
+    }
+
+    #[test]
+    fn test_is_web_safe_default() {
+        assert!(Utf8.is_web_safe_default());
+        assert!(!Windows1252.is_web_safe_default());
+        assert!(!Utf16.is_web_safe_default());
+    }
+
+    #[test]
+    fn test_is_ambiguous_label() {
+        assert!(Charset::is_ambiguous_label("iso-8859-1"));
+        assert!(Charset::is_ambiguous_label("latin1"));
+        assert!(Charset::is_ambiguous_label("UTF-16"));
+        assert!(Charset::is_ambiguous_label("iso-2022-kr"));
+        assert_eq!(None, Utf16.whatwg_name());
+        assert!(!Charset::is_ambiguous_label("utf-8"));
+        assert!(!Charset::is_ambiguous_label("UTF-16LE"));
+        assert!(!Charset::is_ambiguous_label("x-foo"));
+    }
+
+    #[test]
+    fn test_utf8_detection_applicable() {
+        assert!(!Utf16.utf8_detection_applicable());
+        assert!(!Utf32Le.utf8_detection_applicable());
+        assert!(Iso88591.utf8_detection_applicable());
+        assert!(ShiftJis.utf8_detection_applicable());
+    }
+
+    #[test]
+    fn test_with_normalized_case() {
+        assert_eq!("ABC", Unregistered("abc".to_owned()).with_normalized_case().to_string());
+        assert_eq!("utf-8", Utf8.with_normalized_case().to_string());
+        assert_eq!("abc", "abc".parse::<Charset>().unwrap().to_string());
+    }
+
+    #[test]
+    fn test_or_registered() {
+        assert_eq!(Iso88591, Iso88591.or_registered(Utf8));
+        assert_eq!(Utf8, Unregistered("x-foo".to_owned()).or_registered(Utf8));
+    }
+
+    #[test]
+    fn test_into_unregistered() {
+        let charset = UsAscii.into_unregistered();
+        assert!(matches!(charset, Unregistered(ref s) if s == "US-ASCII"));
+        assert_eq!("US-ASCII", charset.to_string());
+        assert_eq!(UsAscii, charset.canonicalize());
+        let unregistered = Unregistered("x-foo".to_owned());
+        assert_eq!(unregistered.clone(), unregistered.into_unregistered());
+    }
+
+    #[test]
+    fn test_eq_ignore_bidi() {
+        assert!(Iso88596E.eq_ignore_bidi(&Iso88596I));
+        assert!(Iso88598.eq_ignore_bidi(&Iso88598I));
+        assert!(Utf8.eq_ignore_bidi(&Utf8));
+        assert!(!Iso88596E.eq_ignore_bidi(&Iso88597));
+        assert!(!Iso88596I.eq_ignore_bidi(&Iso88598I));
+    }
+
+    #[test]
+    fn test_display_name() {
+        assert_eq!("Western European (ISO-8859-1)", Iso88591.display_name());
+        assert_eq!("Japanese (Shift_JIS)", ShiftJis.display_name());
+        assert_eq!("Unicode (UTF-8)", Utf8.display_name());
+        assert_eq!("x-foo", Unregistered("x-foo".to_owned()).display_name());
+    }
+
+    #[test]
+    fn test_enum_ident() {
+        assert_eq!(Some("Iso88591"), Iso88591.enum_ident());
+        for charset in Charset::all() {
+            assert_eq!(Some(&format!("{:?}", charset)[..]), charset.enum_ident());
+        }
+        assert_eq!(None, Unregistered("x-foo".to_owned()).enum_ident());
+    }
+
+    #[test]
+    fn test_same_encoding() {
+        let latin1 = Unregistered("latin1".to_owned());
+        assert!(latin1.same_encoding(&Iso88591));
+        assert!(Iso88591.same_encoding(&latin1));
+        assert!(Iso88598E.same_encoding(&Iso88598I));
+        assert!(Unregistered("csISO88596E".to_owned()).same_encoding(&Iso88596I));
+        assert!(!Iso88596E.same_encoding(&Iso88598E));
+        assert!(!latin1.same_encoding(&Windows1252));
+    }
+
+    #[test]
+    fn test_group() {
+        assert_eq!(CharsetGroup::Greek, Iso88597.group());
+        assert_eq!(CharsetGroup::Cjk, EucJp.group());
+        assert_eq!(CharsetGroup::Unicode, Utf16Le.group());
+        assert_eq!(CharsetGroup::Other, Unregistered("x-foo".to_owned()).group());
+    }
+
+    #[test]
+    fn test_name_len() {
+        assert!(MAX_NAME_LEN >= "ISO-2022-JP-2".len());
+        assert!(Charset::registry().iter().any(|info| info.name.len() == MAX_NAME_LEN));
+        for info in Charset::registry() {
+            assert_eq!(info.name, info.charset.name());
+            assert_eq!(info.name.len(), info.charset.name_len());
+            assert_eq!(Some(info.name.as_bytes()), info.charset.canonical_bytes());
+            assert!(info.charset.name_len() <= MAX_NAME_LEN);
+        }
+        assert_eq!(5, Unregistered("x-foo".to_owned()).name_len());
+    }
+
+    #[test]
+    fn test_from_percent_encoded() {
+        assert_eq!(Some(Utf8), Charset::from_percent_encoded("UTF%2D8"));
+        assert_eq!(Some(Iso88591), Charset::from_percent_encoded("latin1"));
+        assert_eq!(None, Charset::from_percent_encoded("utf-8%C3%A9"));
+        assert_eq!(None, Charset::from_percent_encoded("utf%2"));
+        assert_eq!(None, Charset::from_percent_encoded("utf%+1"));
+        assert_eq!(None, Charset::from_percent_encoded(""));
+    }
+
+    #[test]
+    fn test_grouped_sort_key() {
+        let mut charsets = vec![ShiftJis, Iso88591, Big5, Utf8, EucKr, Koi8R, UsAscii, Gb2312];
+        charsets.sort_by(|a, b| a.grouped_sort_key().cmp(&b.grouped_sort_key()));
+        assert_eq!(vec![Utf8, Iso88591, UsAscii, Koi8R, Big5, EucKr, Gb2312, ShiftJis],
+                   charsets);
+        let cjk: Vec<usize> = charsets.iter()
+                                      .enumerate()
+                                      .filter(|&(_, c)| c.group() == CharsetGroup::Cjk)
+                                      .map(|(i, _)| i)
+                                      .collect();
+        assert_eq!(vec![4, 5, 6, 7], cjk);
+    }
+
+    #[test]
+    fn test_canonical_bytes() {
+        const ASCII: Option<&[u8]> = UsAscii.canonical_bytes();
+        assert_eq!(Some(&b"US-ASCII"[..]), ASCII);
+        assert_eq!(Some(&b"ISO-8859-1"[..]), Iso88591.canonical_bytes());
+        assert_eq!(None, Unregistered("x-foo".to_owned()).canonical_bytes());
+    }
+
+    #[test]
+    fn test_estimate_encoded_len() {
+        let text = "caf\u{e9} \u{1f600}";
+        let estimate = Utf8.estimate_encoded_len(text).unwrap();
+        assert!(estimate >= text.len());
+        assert_eq!(Some(24), Utf8.estimate_encoded_len(text));
+        assert_eq!(Some(6), Iso88591.estimate_encoded_len(text));
+        assert_eq!(Some(0), ShiftJis.estimate_encoded_len(""));
+        assert_eq!(None, Unregistered("x-foo".to_owned()).estimate_encoded_len(text));
+    }
+
+    #[test]
+    fn test_roundtrips_through_unicode() {
+        assert!(Iso88591.roundtrips_through_unicode());
+        assert!(Utf16Le.roundtrips_through_unicode());
+        assert!(!ShiftJis.roundtrips_through_unicode());
+        assert!(!Iso2022Jp.roundtrips_through_unicode());
+        assert!(!Unregistered("x-foo".to_owned()).roundtrips_through_unicode());
+    }
+
+    #[test]
+    fn test_upper_name() {
+        match UsAscii.upper_name() {
+            Cow::Borrowed(s) => assert_eq!("US-ASCII", s),
+            Cow::Owned(_) => panic!("expected borrowed name"),
+        }
+        assert_eq!("UTF-8", Utf8.upper_name());
+        match Unregistered("x-foo".to_owned()).upper_name() {
+            Cow::Owned(s) => assert_eq!("X-FOO", s),
+            Cow::Borrowed(_) => panic!("expected owned name"),
+        }
+    }
+
+    #[test]
+    fn test_display_alternate() {
+        assert_eq!("WINDOWS-1252", format!("{}", Windows1252));
+        assert_eq!("windows-1252", format!("{:#}", Windows1252));
+        assert_eq!("Shift-JIS", format!("{}", ShiftJis));
+        assert_eq!("Shift_JIS", format!("{:#}", ShiftJis));
+        assert_eq!("US-ASCII", format!("{:#}", UsAscii));
+        assert_eq!("x-foo", format!("{:#}", Unregistered("x-foo".to_owned())));
+    }
+
+    #[test]
+    fn test_cmp() {
+        assert!(Iso88593 == Iso88593);
+        assert!(UsAscii != Iso88593);
+        assert_eq!(Unregistered("foobar".to_owned()),
+                   Unregistered("FOOBAR".to_owned()));
+    }
+
+    #[test]
+    fn test_write_bom() {
+        let mut out = Vec::new();
+        Utf8.write_bom(&mut out).unwrap();
+        assert_eq!(b"\xef\xbb\xbf", &out[..]);
+        let mut out = Vec::new();
+        Iso88591.write_bom(&mut out).unwrap();
+        assert!(out.is_empty());
+        assert_eq!(Some(Utf16Be), Utf16.bom().and_then(Charset::from_bom));
+        assert_eq!(None, Utf16Le.bom());
+    }
+
+    #[test]
+    fn test_strip_bom() {
+        assert_eq!(b"abc", Utf8.strip_bom(b"\xef\xbb\xbfabc"));
+        assert_eq!(b"abc", Utf8.strip_bom(b"abc"));
+        assert_eq!(b"\xef\xbb\xbfabc", Iso88591.strip_bom(b"\xef\xbb\xbfabc"));
+        assert_eq!(b"a\x00", Utf16.strip_bom(b"\xff\xfea\x00"));
+        assert_eq!(b"\x00\x00", Utf16Le.strip_bom(b"\xff\xfe\x00\x00"));
+        assert_eq!(b"\xfe\xff\x00a", Utf16Le.strip_bom(b"\xfe\xff\x00a"));
+    }
+
+    #[test]
+    fn test_parse_xml_declaration() {
+        assert_eq!(Some(Utf8),
+                   Charset::parse_xml_declaration(b"\xef\xbb\xbf<?xml version=\"1.0\"?>"));
+        assert_eq!(Some(Iso88591),
+                   Charset::parse_xml_declaration(b"<?xml version=\"1.0\" \
+                                                    encoding=\"ISO-8859-1\"?>"));
+        assert_eq!(Some(ShiftJis),
+                   Charset::parse_xml_declaration(b"<?xml version='1.0' encoding='Shift_JIS'?>"));
+        assert_eq!(None,
+                   Charset::parse_xml_declaration(b"<?xml version=\"1.0\"?><a encoding=\"x\"/>"));
+        assert_eq!(None, Charset::parse_xml_declaration(b"<html>"));
+        assert_eq!(None,
+                   Charset::parse_xml_declaration(b"<?xml-stylesheet href='a.xsl' \
+                                                    encoding='latin1'?>"));
+        assert_eq!(Some(Utf32Le), Charset::from_bom(b"\xff\xfe\x00\x00"));
+        assert_eq!(Some(Utf16Le), Charset::from_bom(b"\xff\xfea\x00"));
+    }
+
+    #[test]
+    fn test_sniff_from_reader() {
+        use std::io::Cursor;
+
+        let mut reader = Cursor::new(b"\xef\xbb\xbfhello".to_vec());
+        let (charset, prefix) = Charset::sniff_from_reader(&mut reader).unwrap();
+        assert_eq!(Utf8, charset);
+        assert_eq!(b"\xef\xbb\xbfhello", &prefix[..]);
+        let (charset, _) = Charset::sniff_from_reader(&mut Cursor::new(b"\xfe\xff")).unwrap();
+        assert_eq!(Utf16Be, charset);
+        let mut html = b"<meta charset=latin1>".to_vec();
+        html.extend(vec![b'a'; 2000]);
+        let mut reader = Cursor::new(html);
+        let (charset, prefix) = Charset::sniff_from_reader(&mut reader).unwrap();
+        assert_eq!(Iso88591, charset);
+        assert_eq!(1024, prefix.len());
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(2021 - 1024, rest.len());
+    }
+
+    #[test]
+    fn test_parse_meta_charset() {
+        assert_eq!(Some(Utf8),
+                   Charset::parse_meta_charset(b"<html><head><meta charset=\"utf-8\">"));
+        assert_eq!(Some(Iso88591),
+                   Charset::parse_meta_charset(b"<META HTTP-EQUIV=\"Content-Type\" \
+                                                 CONTENT=\"text/html; charset=ISO-8859-1\">"));
+        assert_eq!(Some(ShiftJis),
+                   Charset::parse_meta_charset(b"<meta name=x><meta charset = 'Shift_JIS'>"));
+        assert_eq!(None, Charset::parse_meta_charset(b"<meta name=\"charset\">"));
+        assert_eq!(None, Charset::parse_meta_charset(b"<p>charset=utf-8</p>"));
+        assert_eq!(None, Charset::parse_meta_charset(b"<metadata charset=latin1>"));
+        assert_eq!(Some(Utf8), Charset::parse_meta_charset(b"<metadata><meta/charset=utf-8>"));
+    }
+
+    #[test]
+    fn test_from_lenient() {
+        assert_eq!(Utf8, Charset::from_lenient("utf-8;"));
+        assert_eq!(Utf8, Charset::from_lenient("'utf-8'"));
+        assert_eq!(Utf8, Charset::from_lenient(" <\"UTF-8\">; "));
+        assert_eq!(Unregistered("utf-8;".to_owned()), "utf-8;".parse().unwrap());
+        assert_eq!(Unregistered("a".repeat(MAX_LABEL_LEN)),
+                   Charset::from_lenient(&"a".repeat(10000)));
+        assert_eq!(Unregistered(format!("a{}", "\u{e9}".repeat(63))),
+                   Charset::from_lenient(&format!("a{}", "\u{e9}".repeat(100))));
+    }
+
+    #[test]
+    fn test_parse_with_fallback() {
+        assert_eq!(Iso88591, Charset::parse_with_fallback(" latin1 ", Utf8));
+        assert_eq!(Utf8, Charset::parse_with_fallback("x-unknown", Utf8));
+        assert_eq!(Utf8, Charset::parse_with_fallback("  ", Utf8));
+    }
+
+    #[test]
+    fn test_from_java_name() {
+        assert_eq!(Some(ShiftJis), Charset::from_java_name("SJIS"));
+        assert_eq!(Some(Iso88591), Charset::from_java_name("ISO8859_1"));
+        assert_eq!(Some(EucJp), Charset::from_java_name("EUC_JP"));
+        assert_eq!(Some(Gbk), Charset::from_java_name("GBK"));
+        assert_eq!(Some(Utf16Le), Charset::from_java_name("UTF_16LE"));
+        assert_eq!(None, Charset::from_java_name("x-IBM1047"));
+    }
+
+    #[test]
+    fn test_from_python_name() {
+        assert_eq!(Some(Iso88591), Charset::from_python_name("latin_1"));
+        assert_eq!(Some(Iso88591), Charset::from_python_name("Latin-1"));
+        assert_eq!(Some(Windows1252), Charset::from_python_name("cp1252"));
+        assert_eq!(Some(ShiftJis), Charset::from_python_name("shift_jis"));
+        assert_eq!(Some(EucJp), Charset::from_python_name("euc_jp"));
+        assert_eq!(Some(Gb2312), Charset::from_python_name("gb2312"));
+        assert_eq!(Some(ShiftJis), Charset::from_python_name("ms_kanji"));
+        assert_eq!(None, Charset::from_python_name("rot_13"));
+    }
+
+    #[test]
+    fn test_sniff_coding_cookie() {
+        assert_eq!(Some(Iso88591),
+                   Charset::sniff_coding_cookie(b"#!/usr/bin/python\n# -*- coding: latin-1 -*-\n"));
+        assert_eq!(Some(ShiftJis),
+                   Charset::sniff_coding_cookie(b";; -*- coding: japanese-shift-jis -*-"));
+        assert_eq!(Some(Utf8), Charset::sniff_coding_cookie(b"# vim: set fileencoding=utf-8 :"));
+        assert_eq!(None, Charset::sniff_coding_cookie(b"#!/bin/sh\nls\n# coding: utf-8\n"));
+        assert_eq!(None, Charset::sniff_coding_cookie(b"# decoding things\n"));
+        assert_eq!(None, Charset::sniff_coding_cookie(b"x = 'encoding: latin-1'\n"));
+        assert_eq!(None, Charset::sniff_coding_cookie(b"/* -*- mode: c -*- coding: utf-8 */"));
+    }
+
+    #[test]
+    fn test_from_content_type_strict() {
+        assert_eq!(Ok(Some(Utf8)), Charset::from_content_type_strict("text/html; charset=utf-8"));
+        assert_eq!(Ok(Some(Iso88591)),
+                   Charset::from_content_type_strict("text/plain;x=\"a;\\\"b\";Charset=latin1"));
+        assert_eq!(Ok(None), Charset::from_content_type_strict("text/plain; format=flowed"));
+        assert_eq!(Ok(None), Charset::from_content_type_strict("application/json"));
+        assert_eq!(Err(Error::Invalid("text/plain; charset=\"utf-8".to_owned())),
+                   Charset::from_content_type_strict("text/plain; charset=\"utf-8"));
+        assert!(Charset::from_content_type_strict("text/plain; charset=utf 8").is_err());
+        assert!(Charset::from_content_type_strict("text/plain; charset=").is_err());
+        assert!(Charset::from_content_type_strict("text; charset=utf-8").is_err());
+        assert!(Charset::from_content_type_strict("text/plain; =utf-8").is_err());
+        assert!(Charset::from_content_type_strict("text/plain;").is_err());
+        assert!(Charset::from_content_type_strict("text/plain;; charset=utf-8").is_err());
+        assert!(Charset::from_content_type_strict("text/plain; charset=utf-8; charset=latin1")
+                    .is_err());
+    }
+
+    #[test]
+    fn test_resolve() {
+        let bom = b"\xef\xbb\xbf{}";
+        assert_eq!(Iso88591, Charset::resolve("text/plain", Some("latin1"), bom));
+        assert_eq!(Utf8, Charset::resolve("text/plain", None, bom));
+        assert_eq!(Utf8, Charset::resolve("text/plain", Some(" "), bom));
+        assert_eq!(Utf8, Charset::resolve("application/json", None, b"{}"));
+        assert_eq!(Utf8, Charset::resolve("Application/LD+JSON; profile=x", None, b"{}"));
+        assert_eq!(Utf8, Charset::resolve("image/svg+xml", None, b"<svg/>"));
+        assert_eq!(UsAscii, Charset::resolve("text/plain", None, b"hello"));
+        assert_eq!(UsAscii, Charset::resolve("application/octet-stream", None, b""));
+    }
+
+    #[test]
+    fn test_to_content_type() {
+        assert_eq!("text/html; charset=UTF-8", Utf8.to_content_type("text/html"));
+        assert_eq!("text/plain; charset=ISO-8859-1", Iso88591.to_content_type("text/plain"));
+        assert_eq!("text/plain; charset=\"x foo\\\"\"",
+                   Unregistered("x foo\"".to_owned()).to_content_type("text/plain"));
+        let value = Unregistered("x;foo".to_owned()).to_content_type("text/plain");
+        assert_eq!(Ok(Some(Unregistered("x;foo".to_owned()))),
+                   Charset::from_content_type_strict(&value));
+    }
+
+    #[test]
+    fn test_from_content_type() {
+        assert_eq!(Some(Utf8), Charset::from_content_type("text/plain; charset=utf-8 (Unicode)"));
+        assert_eq!(Some(Utf8),
+                   Charset::from_content_type("text/plain (a (nested) comment); charset=(x)utf-8"));
+        assert_eq!(Some(Iso88591), Charset::from_content_type("text/plain; charset=\"latin1\""));
+        assert_eq!(None, Charset::from_content_type("text/plain (charset=utf-8)"));
+        assert_eq!(Some(Utf8),
+                   Charset::from_content_type("text/plain; x=\"(a; charset=koi8-r\"; \
+                                               charset=utf-8"));
+        assert_eq!(None, Charset::from_content_type("text/plain; charset="));
+        assert_eq!(None, Charset::from_content_type("text/plain"));
+    }
+
+    #[test]
+    fn test_from_content_type_or() {
+        assert_eq!(Iso88591, Charset::from_content_type_or("text/plain; charset=latin1", Utf8));
+        assert_eq!(Utf8, Charset::from_content_type_or("text/plain", Utf8));
+        assert_eq!(Utf8, Charset::from_content_type_or("text/plain; charset=\"\"", Utf8));
+    }
+
+    #[test]
+    fn test_content_type_charsets() {
+        assert_eq!(vec![Utf8, Iso88591],
+                   Charset::content_type_charsets("text/html; charset=utf-8; Charset=\"latin1\""));
+        assert_eq!(vec![Utf8], Charset::content_type_charsets("text/plain;charset=UTF-8"));
+        assert!(Charset::content_type_charsets("text/plain; format=flowed").is_empty());
+        assert!(Charset::content_type_charsets("charset=utf-8").is_empty());
+        assert_eq!(vec![Utf8],
+                   Charset::content_type_charsets("text/plain; x=\"a; charset=koi8-r\"; \
+                                                   charset=utf-8"));
+        let charset = Unregistered("x;foo".to_owned());
+        assert_eq!(vec![charset.clone()],
+                   Charset::content_type_charsets(&charset.to_content_type("text/plain")));
+        assert!(Charset::content_type_charsets("text/plain; charset=").is_empty());
+        assert!(Charset::content_type_charsets("text/plain; charset=\"\"").is_empty());
+    }
+
+    #[test]
+    fn test_parse_relaxed() {
+        assert_eq!(Utf8, Charset::parse_relaxed("UTF_8"));
+        assert_eq!(Iso88591, Charset::parse_relaxed("ISO_8859-1"));
+        assert_eq!(EucJp, Charset::parse_relaxed("EUC_JP"));
+        assert_eq!(ShiftJis, Charset::parse_relaxed("Shift_JIS"));
+        assert_eq!(Unregistered("x_foo".to_owned()), Charset::parse_relaxed("x_foo"));
+        assert_eq!(Unregistered("a".repeat(MAX_LABEL_LEN)),
+                   Charset::parse_relaxed(&"a".repeat(10000)));
+    }
+
+    #[test]
+    fn test_from_emacs_coding_system() {
+        assert_eq!(Some(Utf8), Charset::from_emacs_coding_system("utf-8-unix"));
+        assert_eq!(Some(ShiftJis), Charset::from_emacs_coding_system("japanese-shift-jis"));
+        assert_eq!(Some(Iso88591), Charset::from_emacs_coding_system("latin-1-dos"));
+        assert_eq!(Some(EucJp), Charset::from_emacs_coding_system("euc-jp"));
+        assert_eq!(None, Charset::from_emacs_coding_system("emacs-internal"));
+    }
+
+    #[test]
+    fn test_parse_accept_charset() {
+        assert_eq!(vec![(Iso88591, 1.0), (Utf8, 0.5), (Unregistered("*".to_owned()), 0.1)],
+                   Charset::parse_accept_charset("iso-8859-1, utf-8;q=0.5, *;Q=0.1"));
+        assert_eq!(vec![(Utf8, 1.0)],
+                   Charset::parse_accept_charset("utf-8, , latin1;q=2, big5;q=x"));
+    }
+
+    #[test]
+    fn test_negotiate() {
+        let accept = Charset::parse_accept_charset("iso-8859-1;q=0.5, utf-8, big5;q=0");
+        assert_eq!(Some(Utf8), Charset::negotiate(&accept, &[Iso88591, Utf8]));
+        assert_eq!(Some(Iso88591), Charset::negotiate(&accept, &[Iso88591, Big5]));
+        assert_eq!(None, Charset::negotiate(&accept, &[Big5, ShiftJis]));
+        let accept = Charset::parse_accept_charset("utf-8;q=0.5, *;q=0.8");
+        assert_eq!(Some(ShiftJis), Charset::negotiate(&accept, &[Utf8, ShiftJis]));
+        assert_eq!(Some(Big5), Charset::negotiate(&[], &[Big5, Utf8]));
+        let accept = Charset::parse_accept_charset("utf-16, utf-8, big5, euc-jp");
+        assert_eq!(Some(Utf8), Charset::negotiate(&accept, &[Utf16, Utf8]));
+        assert_eq!(Some(Big5), Charset::negotiate(&accept, &[EucJp, Big5]));
+        assert_eq!(Some(Utf8), Charset::negotiate(&[(Unregistered("*".to_owned()), 1.0)],
+                                                  &[Iso88591, Utf8]));
+    }
+
+    #[test]
+    fn test_parse_accept_charset_multi() {
+        assert_eq!(vec![(Utf8, 1.0), (Iso88591, 0.5), (Big5, 0.2), (ShiftJis, 1.0)],
+                   Charset::parse_accept_charset_multi(&["utf-8, latin1;q=0.5",
+                                                         "big5;q=0.2, shift_jis"]));
+        assert!(Charset::parse_accept_charset_multi(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_parse_accept_charset_sorted() {
+        let sorted = Charset::parse_accept_charset_sorted("big5;q=0.5, euc-jp, latin1;q=0, \
+                                                           shift_jis, utf-8");
+        assert_eq!(vec![(Utf8, 1.0), (EucJp, 1.0), (ShiftJis, 1.0), (Big5, 0.5)], sorted);
+    }
+
+    #[test]
+    fn test_format_with_quality() {
+        assert_eq!("utf-8", Utf8.format_with_quality(1.0));
+        assert_eq!("ISO-8859-1;q=0.5", Iso88591.format_with_quality(0.5));
+        assert_eq!("ISO-8859-1;q=0.125", Iso88591.format_with_quality(0.125));
+        assert_eq!("Big5;q=0", Big5.format_with_quality(-1.0));
+        assert_eq!("Big5", Big5.format_with_quality(2.0));
+        assert_eq!("Big5;q=0", Big5.format_with_quality(f32::NAN));
+    }
+
+    #[test]
+    fn test_merge_preferences() {
+        let client = Charset::parse_accept_charset("utf-8;q=0.5, latin1, big5, *;q=0.1");
+        let server = [(Iso88591, 0.4), (Utf8, 1.0), (ShiftJis, 1.0), (EucJp, 0.0)];
+        assert_eq!(vec![(Utf8, 0.5), (Iso88591, 0.4), (ShiftJis, 0.1)],
+                   Charset::merge_preferences(&client, &server));
+        assert!(Charset::merge_preferences(&[(Big5, 1.0)], &server).is_empty());
+    }
+
+    #[test]
+    fn test_preferred_over() {
+        assert!(Utf8.preferred_over(&Utf16Le));
+        assert!(Utf16.preferred_over(&Utf32));
+        assert!(Utf32Be.preferred_over(&Iso88591));
+        assert!(!Iso88591.preferred_over(&Windows1252));
+        assert!(!Windows1252.preferred_over(&Iso88591));
+        assert!(!Utf8.preferred_over(&Utf8));
+    }
+
+    #[test]
+    fn test_charset_preference() {
+        let mut preference = CharsetPreference::new();
+        preference.push(Iso88591, 0.5);
+        preference.push(ShiftJis, 0.9);
+        preference.push(EucJp, 0.9);
+        assert_eq!(Some(ShiftJis), preference.best_supported(&[EucJp, Iso88591, ShiftJis]));
+        assert_eq!(Some(EucJp), preference.best_supported(&[EucJp, Iso88591]));
+        assert_eq!(Some(Iso88591), preference.best_supported(&[Utf8, Iso88591]));
+        preference.extend(vec![(Utf8, 1.0)]);
+        assert_eq!(Some(Utf8), preference.best_supported(&[Iso88591, Utf8]));
+        assert_eq!(None, preference.best_supported(&[Big5]));
+    }
+
+    #[test]
+    fn test_normalize_label() {
+        assert_eq!("ISO-8859-1", Charset::normalize_label(" Latin1 "));
+        assert_eq!("ISO-8859-1", Charset::normalize_label("iso-8859-1"));
+        assert_eq!("x-Custom", Charset::normalize_label("  x-Custom\t"));
+    }
+
+    #[test]
+    fn test_parse_all() {
+        assert_eq!(vec![Utf8, Iso88591, Big5],
+                   Charset::parse_all("utf-8, iso-8859-1, ,big5"));
+        assert!(Charset::parse_all("").is_empty());
+    }
+
+    #[test]
+    fn test_ord() {
+        let set: BTreeSet<Charset> = vec![Unregistered("x-b".to_owned()),
+                                          Gb2312,
+                                          Utf8,
+                                          Unregistered("X-A".to_owned()),
+                                          ShiftJis,
+                                          Iso88591,
+                                          UsAscii]
+                                         .into_iter()
+                                         .collect();
+        assert_eq!(vec![UsAscii,
+                        Iso88591,
+                        ShiftJis,
+                        Utf8,
+                        Gb2312,
+                        Unregistered("x-a".to_owned()),
+                        Unregistered("x-b".to_owned())],
+                   set.into_iter().collect::<Vec<_>>());
+        assert_eq!(Ordering::Equal,
+                   Unregistered("foo".to_owned()).cmp(&Unregistered("FOO".to_owned())));
+    }
+
+    #[test]
+    fn test_hash() {
+        let mut set = HashSet::new();
+        set.insert(Unregistered("X-Foo".to_owned()));
+        set.insert(Unregistered("x-foo".to_owned()));
+        assert_eq!(1, set.len());
+        set.insert(Utf8);
+        set.insert(Utf8);
+        assert_eq!(2, set.len());
+    }
+
+    #[test]
+    fn test_error_source() {
+        assert!(Error::Invalid("x".to_owned()).source().is_none());
+        let err = Error::Decode(DecodeError { charset: Utf8 });
+        assert_eq!("input could not be decoded as utf-8",
+                   err.source().unwrap().to_string());
+    }
+
+    #[cfg(feature = "encoding_rs")]
+    #[test]
+    fn test_decode() {
+        assert_eq!("caf\u{e9}", Iso88591.decode(b"caf\xe9").unwrap());
+        assert_eq!("\u{80}\u{9f}", Iso88591.decode(b"\x80\x9f").unwrap());
+        assert_eq!("\u{20ac}", Iso88591.web_decoding_charset().decode(b"\x80").unwrap());
+        let decoded = Iso88591.decode_strict(b"\x80\x9f\xff").unwrap();
+        let mut encoder = Iso88591.new_encoder().unwrap();
+        assert_eq!((b"\x80\x9f\xff".to_vec(), false), encoder.encode_chunk(&decoded, true));
+        assert_eq!("caf\u{fffd}", UsAscii.decode(b"caf\xe9").unwrap());
+        assert!(UsAscii.decode_strict(b"caf\xe9").is_err());
+        assert_eq!("cafe", UsAscii.decode_strict(b"cafe").unwrap());
+        assert_eq!("\u{fffd}", Utf8.decode(b"\xff").unwrap());
+        assert!(Iso2022Kr.decode(b"abc").is_none());
+        assert_eq!("abc", Utf8.decode_strict(b"abc").unwrap());
+        let err = Utf8.decode_strict(b"\xff").unwrap_err();
+        assert!(err.source().is_some());
+    }
+
+    #[cfg(feature = "encoding_rs")]
+    #[test]
+    fn test_decode_bom_aware() {
+        assert_eq!(Some(Cow::Borrowed("caf\u{e9}")),
+                   Iso88591.decode_bom_aware(b"\xef\xbb\xbfcaf\xc3\xa9"));
+        assert_eq!(Some(Cow::Borrowed("caf\u{e9}")),
+                   Utf8.decode_bom_aware(b"\xff\xfec\x00a\x00f\x00\xe9\x00"));
+        assert_eq!(Some(Cow::Borrowed("caf\u{c3}\u{a9}")),
+                   Iso88591.decode_bom_aware(b"caf\xc3\xa9"));
+        assert_eq!(Some(Cow::Borrowed("\u{0}a\u{0}")),
+                   Utf8.decode_bom_aware(b"\xff\xfe\x00\x00a\x00\x00\x00"));
+        assert_eq!(Some(Cow::Borrowed("\u{0}")), Utf16Le.decode_bom_aware(b"\xff\xfe\x00\x00"));
+    }
+
+    #[cfg(feature = "encoding_rs")]
+    #[test]
+    fn test_decode_fast() {
+        match Iso88591.decode_fast(b"plain ascii") {
+            Some(Cow::Borrowed(s)) => assert_eq!("plain ascii", s),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!("caf\u{e9}", Iso88591.decode_fast(b"caf\xe9").unwrap());
+        assert_eq!("a", Utf16Be.decode_fast(b"\x00a").unwrap());
+    }
+
+    #[cfg(feature = "encoding_rs")]
+    #[test]
+    fn test_decode_utf16_bom() {
+        assert_eq!("hi", Utf16.decode(b"\xff\xfeh\x00i\x00").unwrap());
+        assert_eq!("hi", Utf16.decode(b"\xfe\xff\x00h\x00i").unwrap());
+        assert_eq!("hi", Utf16.decode(b"\x00h\x00i").unwrap());
+        assert_eq!("hi", Utf16.decode_strict(b"\xff\xfeh\x00i\x00").unwrap());
+        assert_eq!("\u{feff}hi", Utf16Le.decode(b"\xff\xfeh\x00i\x00").unwrap());
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_header_value() {
+        assert_eq!("utf-8", HeaderValue::try_from(Utf8).unwrap());
+        assert!(HeaderValue::try_from(Unregistered("a\nb".to_owned())).is_err());
+        assert_eq!("text/html; charset=UTF-8",
+                   Utf8.to_content_type_header("text/html").unwrap());
+        assert_eq!("text/plain; charset=\"x foo\"",
+                   Unregistered("x foo".to_owned()).to_content_type_header("text/plain").unwrap());
+    }
+
+    #[cfg(feature = "mime")]
+    #[test]
+    fn test_mime() {
+        assert_eq!(Utf8, Charset::from_mime(mime::UTF_8));
+        for charset in &[Utf8, Iso88591] {
+            let m: mime::Mime = format!("text/plain; charset={}", charset.to_mime_string())
+                                    .parse()
+                                    .unwrap();
+            assert_eq!(charset, &Charset::from_mime(m.get_param(mime::CHARSET).unwrap()));
+        }
+        assert_eq!("utf-8", Utf8.to_mime_string());
+    }
+
+    #[cfg(feature = "encoding_rs")]
+    #[test]
+    fn test_decoder() {
+        let mut decoder = Utf8.new_decoder().unwrap();
+        assert_eq!("a", decoder.decode_chunk(b"a\xe2", false));
+        assert_eq!("\u{20ac}b", decoder.decode_chunk(b"\x82\xacb", false));
+        assert_eq!("\u{fffd}", decoder.decode_chunk(b"\xe2", true));
+        let mut decoder = ShiftJis.new_decoder().unwrap();
+        assert_eq!("", decoder.decode_chunk(b"\x88", false));
+        assert_eq!("\u{4e9c}", decoder.decode_chunk(b"\x9f", true));
+        assert!(Iso2022Kr.new_decoder().is_none());
+        for charset in &[UsAscii, Iso88591] {
+            let mut decoder = charset.new_decoder().unwrap();
+            assert_eq!(charset.decode(b"a\x80\xff").unwrap(),
+                       decoder.decode_chunk(b"a\x80\xff", true));
+        }
+        let mut decoder = Utf16.new_decoder().unwrap();
+        assert_eq!("", decoder.decode_chunk(b"\xff", false));
+        assert_eq!("h", decoder.decode_chunk(b"\xfeh\x00i", false));
+        assert_eq!("i", decoder.decode_chunk(b"\x00", true));
+        let mut decoder = Utf16.new_decoder().unwrap();
+        assert_eq!(Utf16.decode(b"\xef\xbb\xbfab").unwrap(),
+                   decoder.decode_chunk(b"\xef\xbb\xbfab", true));
+    }
+
+    #[cfg(feature = "encoding_rs")]
+    #[test]
+    fn test_encoder() {
+        let mut encoder = Iso2022Jp.new_encoder().unwrap();
+        let (mut bytes, replaced) = encoder.encode_chunk("a\u{65e5}", false);
+        assert!(!replaced);
+        bytes.extend(encoder.encode_chunk("\u{672c}", false).0);
+        bytes.extend(encoder.encode_chunk("", true).0);
+        assert_eq!(&b"a\x1b$BF|K\\\x1b(B"[..], &bytes[..]);
+        let mut encoder = Iso88591.new_encoder().unwrap();
+        assert_eq!((b"caf\xe9".to_vec(), false), encoder.encode_chunk("caf\u{e9}", false));
+        assert_eq!((b"&#128512;".to_vec(), true), encoder.encode_chunk("\u{1f600}", true));
+        assert!(Utf16.new_encoder().is_none());
+        let mut encoder = UsAscii.new_encoder().unwrap();
+        assert_eq!((b"caf&#233;".to_vec(), true), encoder.encode_chunk("caf\u{e9}", true));
+    }
+
+    #[cfg(feature = "encoding_rs")]
+    #[test]
+    fn test_encoding_rs_name() {
+        assert_eq!(Some("windows-1252"), Iso88591.encoding_rs_name());
+        for charset in Charset::all() {
+            if let Some(whatwg_name) = charset.whatwg_name() {
+                let name = charset.encoding_rs_name().unwrap();
+                assert!(name.eq_ignore_ascii_case(whatwg_name), "{} {}", charset, name);
+            }
+        }
+        assert_eq!(None, Utf32.encoding_rs_name());
+    }
+
+    #[cfg(feature = "encoding_rs")]
+    #[test]
+    fn test_recommended_for() {
+        assert_eq!(UsAscii, Charset::recommended_for("plain text"));
+        assert_eq!(Iso88591, Charset::recommended_for("caf\u{e9}"));
+        assert_eq!(Windows1252, Charset::recommended_for("5 \u{20ac}"));
+        assert_eq!(Utf8, Charset::recommended_for("smile \u{1f600}"));
+    }
+
+    #[cfg(feature = "encoding_rs")]
+    #[test]
+    fn test_can_encode_lossless() {
+        assert_eq!(Some(false), Iso88591.can_encode_lossless("smile \u{1f600}"));
+        assert_eq!(Some(true), Iso88591.can_encode_lossless("caf\u{e9}"));
+        assert_eq!(Some(false), UsAscii.can_encode_lossless("caf\u{e9}"));
+        assert_eq!(Some(true), Windows1252.can_encode_lossless("\u{20ac}"));
+        assert_eq!(Some(false), Iso88591.can_encode_lossless("\u{20ac}"));
+        assert_eq!(Some(true), Iso88591.can_encode_lossless("\u{80}"));
+        assert_eq!(Some(true), ShiftJis.can_encode_lossless("\u{65e5}\u{672c}"));
+        assert_eq!(None, Utf16Le.can_encode_lossless("a"));
+    }
+
+    #[test]
+    fn test_try_from_bytes() {
+        assert_eq!(Ok(UsAscii), Charset::try_from_bytes(b"us-ascii"));
+        assert_eq!(Err(Error::Invalid("a".repeat(MAX_LABEL_LEN))),
+                   Charset::try_from_bytes(&[b'a'; MAX_LABEL_LEN + 1]));
+        assert_eq!(Err(Error::Invalid("utf-\u{fffd}8".to_owned())),
+                   Charset::try_from_bytes(b"utf-\xff8"));
+        assert_eq!(Err(Error::Invalid("\u{e9}".to_owned())),
+                   Charset::try_from_bytes("\u{e9}".as_bytes()));
+        assert_eq!(Err(Error::Invalid("\u{fffd}".repeat(MAX_LABEL_LEN / 3))),
+                   Charset::try_from_bytes(&[0xff; 200]));
+        assert_eq!(Err(Error::Invalid(format!("a{}", "\u{e9}".repeat(63)))),
+                   Charset::try_from_bytes(format!("a{}", "\u{e9}".repeat(100)).as_bytes()));
+    }
+
+    #[test]
+    fn test_suggest() {
+        assert_eq!(Some(Utf8), Charset::suggest("utf8x"));
+        assert_eq!(Some(Iso88591), Charset::suggest("ISO-8859-01"));
+        assert_eq!(Some(ShiftJis), Charset::suggest("shiftjis"));
+        assert_eq!(None, Charset::suggest("zzzz"));
+        assert_eq!(None, Charset::suggest("zz"));
+        assert_eq!(3, edit_distance("kitten", "sitting"));
+    }
+
+    #[test]
+    fn test_parse_bytes_with_limit() {
+        assert_eq!(Ok(Utf8), Charset::parse_bytes_with_limit(b"utf-8", 5));
+        assert_eq!(Ok(Unregistered("x-foo".to_owned())),
+                   Charset::parse_bytes_with_limit(b"x-foo", 16));
+        assert_eq!(Err(Error::Invalid("latin1".to_owned())),
+                   Charset::parse_bytes_with_limit(b"latin1", 5));
+        assert!(Charset::parse_bytes_with_limit(b"caf\xe9", 16).is_err());
+    }
+
+    #[test]
+    fn test_parse_list_partitioned() {
+        assert_eq!((vec![Utf8, Big5], vec!["bogus".to_owned()]),
+                   Charset::parse_list_partitioned("utf-8, bogus, big5"));
+        assert_eq!((vec![], vec![]), Charset::parse_list_partitioned(" , "));
+    }
+
+    #[test]
+    fn test_parse_prefix() {
+        assert_eq!(Some((Utf8, "; boundary=x")), Charset::parse_prefix("utf-8; boundary=x"));
+        assert_eq!(Some((Iso88591, ", utf-8")), Charset::parse_prefix(" latin1, utf-8"));
+        assert_eq!(Some((Big5, "")), Charset::parse_prefix("big5"));
+        assert_eq!(None, Charset::parse_prefix("; x"));
+        assert_eq!(None, Charset::parse_prefix(""));
+    }
+
+    #[test]
+    fn test_from_label_detailed() {
+        assert_eq!(Ok(Iso88591), Charset::from_label_detailed(" latin1 "));
+        assert_eq!(Err(Error::Empty), Charset::from_label_detailed(""));
+        assert_eq!(Err(Error::NonAscii), Charset::from_label_detailed("utf-\u{2178}"));
+        assert_eq!(Err(Error::Unknown("x-foo".to_owned())),
+                   Charset::from_label_detailed("x-foo"));
+        assert_eq!(Err(Error::Invalid("utf 8".to_owned())),
+                   Charset::from_label_detailed("utf 8"));
+        assert_eq!("non-ASCII charset label", Error::NonAscii.to_string());
+        assert_eq!("unknown charset label \"x\"", Error::Unknown("x".to_owned()).to_string());
+    }
+
+    #[test]
+    fn test_parse_strict() {
+        assert_eq!(Ok(Utf8), Charset::parse_strict("utf-8"));
+        assert_eq!(Ok(Iso88591), Charset::parse_strict("latin1"));
+        assert_eq!(Err(Error::Empty), Charset::parse_strict(""));
+        assert_eq!(Err(Error::Empty), Charset::parse_strict(" \t"));
+        assert_eq!(Err(Error::Invalid("utf 8".to_owned())), Charset::parse_strict("utf 8"));
+        assert_eq!(Err(Error::Invalid("x-foo".to_owned())), Charset::parse_strict("x-foo"));
+        assert_eq!(Err(Error::Invalid(" utf-8 ".to_owned())), Charset::parse_strict(" utf-8 "));
+        assert_eq!("empty charset label", Error::Empty.to_string());
+        assert_eq!("invalid charset label \"x\"", Error::Invalid("x".to_owned()).to_string());
     }
 }